@@ -1,8 +1,15 @@
-use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use cirtrace::{ColorChoice, ModuleTypes};
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
 
 pub fn get_args<'a>() -> ArgMatches<'a> {
+    let module_help = format!(
+        "The name of the module process: {} or one described in \
+         --module-types.",
+        ModuleTypes::default().names().join(", ")
+    );
+
     let host = Arg::with_name("host")
-        .required(true)
+        .required_unless("input-file")
         .help("SBC host to connect.")
         .takes_value(true);
 
@@ -11,7 +18,10 @@ pub fn get_args<'a>() -> ArgMatches<'a> {
         .multiple(true)
         .long("search-term")
         .short("S")
-        .help("Filter trace based on this term. Can be phone number or IP.")
+        .help(
+            "Filter trace based on this term. Can be phone number or IP, \
+             or an expression such as 'status>=400'.",
+        )
         .takes_value(true);
 
     let username = Arg::with_name("username")
@@ -22,7 +32,7 @@ pub fn get_args<'a>() -> ArgMatches<'a> {
         .takes_value(true);
 
     let password = Arg::with_name("password")
-        .required(true)
+        .required_unless("input-file")
         .short("p")
         .long("password")
         .help("User password")
@@ -32,24 +42,55 @@ pub fn get_args<'a>() -> ArgMatches<'a> {
         .required(false)
         .short("T")
         .long("trace-time")
-        .help("How long the debug should run for in seconds. Default: 15s")
+        .help(
+            "How long the debug should run for in seconds. Default: 15s, or \
+             --max-trace-time with --interactive",
+        )
+        .validator(seconds)
         .takes_value(true);
 
+    let max_trace_time = Arg::with_name("max-trace-time")
+        .required(false)
+        .long("max-trace-time")
+        .default_value("600")
+        .validator(seconds)
+        .help("Refuse to trace for longer than this many seconds.")
+        .takes_value(true);
+
+    let force = Arg::with_name("force").required(false).long("force").help(
+        "Enable debug even when /home/log is short of space, the host is \
+             loaded or the trace time is over --max-trace-time.",
+    );
+
+    let interactive = Arg::with_name("interactive")
+        .required(false)
+        .long("interactive")
+        .help(
+            "Enable debug when Enter is pressed and trace until Enter or q \
+             is pressed again, at most for --trace-time.",
+        );
+
     let process = Arg::with_name("module")
         .required(false)
         .short("m")
         .long("module")
-        .requires("instance")
-        .possible_values(&["ibcf", "bgcf"])
-        .help("The name of the module process.")
+        .help(&module_help)
+        .takes_value(true);
+
+    let module_types = Arg::with_name("module-types")
+        .required(false)
+        .long("module-types")
+        .help(
+            "JSON file describing how to enable and disable debug on more \
+             process types and where they log.",
+        )
         .takes_value(true);
 
     let process_name = Arg::with_name("module-name")
         .required(false)
         .short("M")
         .long("module-name")
-        .required_unless_all(&["module", "instance"])
-        .required_unless("status")
+        .required_unless_one(&["module", "input-file"])
         .help("The name of the module instance.")
         .takes_value(true);
 
@@ -58,29 +99,234 @@ pub fn get_args<'a>() -> ArgMatches<'a> {
         .short("i")
         .long("instance")
         .requires("module")
-        .help("Process instance")
+        .help("Process instance. Prompts for one if omitted and several match.")
         .takes_value(true);
 
     let output_file = Arg::with_name("output-file")
         .required(false)
         .short("o")
         .long("output-file")
-        .help("Path location to store the output.")
+        .help("Path location to store the output, - for stdout.")
+        .takes_value(true);
+
+    let overwrite = Arg::with_name("overwrite")
+        .required(false)
+        .long("overwrite")
+        .conflicts_with("append")
+        .help("Replace the files written to if they exist.");
+
+    let append = Arg::with_name("append")
+        .required(false)
+        .long("append")
+        .help(
+            "Add to the end of the output file and --redact-map if they \
+             exist.",
+        );
+
+    let save_raw = Arg::with_name("save-raw")
+        .required(false)
+        .long("save-raw")
+        .help(
+            "Also save the full log as fetched, gzipped if the path ends \
+             in .gz. Read it back with --input-file.",
+        )
+        .takes_value(true);
+
+    let input_file = Arg::with_name("input-file")
+        .required(false)
+        .short("f")
+        .long("input-file")
+        .conflicts_with("save-raw")
+        .help("Parse a log saved with --save-raw instead of capturing one.")
+        .takes_value(true);
+
+    let redact = Arg::with_name("redact")
+        .required(false)
+        .long("redact")
+        .help(
+            "Pseudonymise phone numbers and SIP URI users in the output, \
+             consistently across the run.",
+        );
+
+    let redact_ips = Arg::with_name("redact-ips")
+        .required(false)
+        .long("redact-ips")
+        .requires("redact")
+        .help("Pseudonymise IPv4 addresses too.");
+
+    let redact_map = Arg::with_name("redact-map")
+        .required(false)
+        .long("redact-map")
+        .requires("redact")
+        .help("Save the pseudonyms and what they replaced to this file.")
+        .takes_value(true);
+
+    let color_names = ColorChoice::names();
+    let color = Arg::with_name("color")
+        .required(false)
+        .long("color")
+        .possible_values(&color_names)
+        .default_value("auto")
+        .help("Colour the SIP messages. `auto` respects NO_COLOR.")
+        .takes_value(true);
+
+    let search_regex = Arg::with_name("search-regex")
+        .required(false)
+        .multiple(true)
+        .number_of_values(1)
+        .long("search-regex")
+        .help(
+            "Select calls with a URI user or host, Call-ID or ISUP number \
+             matching this regex.",
+        )
+        .takes_value(true);
+
+    let normalize_numbers = Arg::with_name("normalize-numbers")
+        .required(false)
+        .long("normalize-numbers")
+        .value_name("country-code")
+        .validator(|cc| {
+            if cirtrace::e164::is_country_code(&cc) {
+                Ok(())
+            } else {
+                Err(format!("{} is not a country code", cc))
+            }
+        })
+        .help(
+            "Match numbers in any national or international form, \
+             national numbers being in this country, i.e. 353.",
+        )
         .takes_value(true);
 
+    let since = Arg::with_name("since")
+        .required(false)
+        .long("since")
+        .help(
+            "Only show what was logged from this time on: HH:MM:SS or an \
+             offset from the start of the capture, i.e. +30s.",
+        )
+        .takes_value(true);
+
+    let until = Arg::with_name("until")
+        .required(false)
+        .long("until")
+        .help(
+            "Only show what was logged up to this time: HH:MM:SS, HH:MM \
+             for the end of that minute or an offset from the start of the \
+             capture, i.e. +1m30s.",
+        )
+        .takes_value(true);
+
+    let hep = Arg::with_name("hep")
+        .required(false)
+        .long("hep")
+        .value_name("udp://host:port")
+        .help("Also send the SIP messages to this HEPv3 (Homer) server.")
+        .takes_value(true);
+
+    let bundle = Arg::with_name("bundle")
+        .required(false)
+        .long("bundle")
+        .value_name("file.tar.gz")
+        .help(
+            "Also package the raw log, SIP messages as text and pcap, call \
+             summaries, status report and module config in one archive.",
+        )
+        .takes_value(true);
+
+    let until_call_ends = Arg::with_name("until-call-ends")
+        .required(false)
+        .long("until-call-ends")
+        .requires("search")
+        .help(
+            "Stop the capture a few seconds after a matching call ends, or \
+             after --trace-time at the latest.",
+        );
+
+    let sip_format = Arg::with_name("format")
+        .required(false)
+        .long("format")
+        .possible_values(&["text", "summary"])
+        .default_value("text")
+        .help("Print the SIP messages or a summary of each matched call.")
+        .takes_value(true);
+
+    let headers = Arg::with_name("headers")
+        .required(false)
+        .long("headers")
+        .multiple(true)
+        .use_delimiter(true)
+        .conflicts_with("hide-headers")
+        .help("Comma separated list of the only headers to print.")
+        .takes_value(true);
+
+    let hide_headers = Arg::with_name("hide-headers")
+        .required(false)
+        .long("hide-headers")
+        .multiple(true)
+        .use_delimiter(true)
+        .help("Comma separated list of headers not to print.")
+        .takes_value(true);
+
+    let compact = Arg::with_name("compact")
+        .required(false)
+        .long("compact")
+        .conflicts_with_all(&["headers", "hide-headers"])
+        .help(
+            "Print only From, To, Call-ID, CSeq, Contact and an SDP summary.",
+        );
+
     let sip_command = SubCommand::with_name("sip")
         .help("Prints raw captured SIP packets.")
         .settings(&[
             AppSettings::ArgRequiredElseHelp,
             AppSettings::UnifiedHelpMessage,
         ])
-        .arg(search_term.clone());
+        .arg(search_term.clone())
+        .arg(search_regex)
+        .group(
+            ArgGroup::with_name("search")
+                .args(&["search-term", "search-regex"])
+                .multiple(true),
+        )
+        .arg(since.clone())
+        .arg(until.clone())
+        .arg(normalize_numbers)
+        .arg(hep)
+        .arg(bundle)
+        .arg(until_call_ends)
+        .arg(sip_format)
+        .arg(headers)
+        .arg(hide_headers)
+        .arg(compact);
 
     let trace_command = SubCommand::with_name("trace")
         .help("Prints full trace.")
-        .arg(search_term);
+        .arg(search_term.help("Only show log entries containing this term."))
+        .arg(since)
+        .arg(until);
 
-    let status = SubCommand::with_name("status").help("Show remote host info.");
+    let format = Arg::with_name("format")
+        .required(false)
+        .long("format")
+        .possible_values(&["table", "json"])
+        .default_value("table")
+        .help("Output format of the status report.")
+        .takes_value(true);
+
+    let processes = Arg::with_name("processes")
+        .required(false)
+        .short("P")
+        .long("processes")
+        .multiple(true)
+        .use_delimiter(true)
+        .help("Comma separated list of processes to report on.")
+        .takes_value(true);
+
+    let status = SubCommand::with_name("status")
+        .help("Show remote host info.")
+        .arg(format)
+        .arg(processes);
 
     App::new("cir_trace")
         .version("0.1")
@@ -90,13 +336,34 @@ pub fn get_args<'a>() -> ArgMatches<'a> {
         .arg(username)
         .arg(password)
         .arg(trace_time)
+        .arg(max_trace_time)
+        .arg(force)
+        .arg(interactive)
         .arg(process)
+        .arg(module_types)
         .arg(process_name)
         .arg(instance)
         .arg(output_file)
+        .arg(overwrite)
+        .arg(append)
+        .arg(save_raw)
+        .arg(input_file)
+        .arg(redact)
+        .arg(redact_ips)
+        .arg(redact_map)
+        .arg(color)
         .subcommand(sip_command)
         .subcommand(trace_command)
         .subcommand(status)
         .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
         .get_matches()
 }
+
+/// A whole number of seconds.
+fn seconds(value: String) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("{} is not a number of seconds", value)),
+    }
+}
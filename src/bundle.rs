@@ -0,0 +1,157 @@
+//! Capture bundles: everything the vendor asks for when a call is
+//! escalated, in one `.tar.gz`.
+//!
+//! The files go under a directory named after the archive, next to a
+//! `manifest.json` describing the capture and each file.
+
+use crate::error::{Error, TraceResult};
+use crate::output::{Output, WriteMode};
+use crate::raw::CaptureInfo;
+use flate2::{write::GzEncoder, Compression};
+use serde_json::json;
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug)]
+struct BundleFile {
+    name: String,
+    description: String,
+    contents: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct Bundle {
+    info: CaptureInfo,
+    redacted: bool,
+    files: Vec<BundleFile>,
+}
+
+impl Bundle {
+    pub fn new(info: CaptureInfo) -> Self {
+        Self {
+            info,
+            redacted: false,
+            files: Vec::new(),
+        }
+    }
+
+    /// Record in the manifest that the contents were pseudonymised.
+    pub fn redacted(mut self, redacted: bool) -> Self {
+        self.redacted = redacted;
+        self
+    }
+
+    pub fn add<C: Into<Vec<u8>>>(
+        &mut self,
+        name: &str,
+        description: &str,
+        contents: C,
+    ) {
+        self.files.push(BundleFile {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            contents: contents.into(),
+        });
+    }
+
+    pub fn manifest(&self) -> serde_json::Value {
+        let secs = |t: Option<SystemTime>| {
+            t.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+        };
+        let files: Vec<_> = self
+            .files
+            .iter()
+            .map(|f| {
+                json!({
+                    "name": f.name,
+                    "description": f.description,
+                    "size": f.contents.len(),
+                })
+            })
+            .collect();
+
+        json!({
+            "cirtrace_version": env!("CARGO_PKG_VERSION"),
+            "created": secs(Some(SystemTime::now())),
+            "redacted": self.redacted,
+            "capture": {
+                "captured_by": Some(&self.info.version)
+                    .filter(|v| !v.is_empty()),
+                "host": self.info.host,
+                "module": self.info.module,
+                "module_name": self.info.module_name,
+                "instance": self.info.instance,
+                "config_file": self.info.config_file,
+                "start": secs(self.info.start),
+                "end": secs(self.info.end),
+                "remote_start": self.info.remote_start,
+                "remote_unix_time": self.info.remote_unix_time,
+                "utc_offset": self.info.utc_offset,
+            },
+            "files": files,
+        })
+    }
+
+    /// Write the archive to `path`, replacing an existing one only
+    /// with `overwrite`.
+    pub fn write<P: AsRef<Path>>(
+        &self,
+        path: P,
+        overwrite: bool,
+    ) -> TraceResult<()> {
+        let path = path.as_ref();
+        let dir = archive_stem(path);
+        let manifest = serde_json::to_vec_pretty(&self.manifest())
+            .map_err(Error::Serialize)?;
+        let mtime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        let gz = GzEncoder::new(Vec::new(), Compression::default());
+        let mut tar = tar::Builder::new(gz);
+        let files = self
+            .files
+            .iter()
+            .map(|f| (f.name.as_str(), &f.contents[..]))
+            .chain(Some(("manifest.json", &manifest[..])));
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            tar.append_data(&mut header, format!("{}/{}", dir, name), contents)
+                .map_err(Error::Write)?;
+        }
+        let bytes = tar
+            .into_inner()
+            .and_then(|gz| gz.finish())
+            .map_err(Error::Write)?;
+
+        let mode = if overwrite {
+            WriteMode::Overwrite
+        } else {
+            WriteMode::NoClobber
+        };
+        Output::new(path).mode(mode).save_bytes(&bytes)
+    }
+}
+
+/// `call-1234` for `/tmp/call-1234.tar.gz`.
+fn archive_stem(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = [".tar.gz", ".tgz", ".gz", ".tar"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(&name);
+    if stem.is_empty() {
+        "cirtrace-bundle".to_owned()
+    } else {
+        stem.to_owned()
+    }
+}
@@ -0,0 +1,124 @@
+use crate::commands::{CommandRunner, ProcessInfo, RemoteClock};
+use crate::error::TraceResult;
+use std::time::Duration;
+
+/// A running trace of a single process.
+///
+/// `start` turns debug on and remembers the remote time, `finish`
+/// fetches everything logged since then and turns debug off again.
+/// What happens in between (usually placing a test call) is up to
+/// the caller.
+///
+/// Debug is turned off on every way out: when `finish` fails to fetch
+/// the log and when the session is dropped without finishing. For
+/// the client dying altogether, [`CaptureSession::watchdog`] leaves a
+/// job on the host that turns it off after a while.
+pub struct CaptureSession<'a> {
+    runner: &'a CommandRunner,
+    process: ProcessInfo,
+    /// Remote clock when the capture started.
+    clock: RemoteClock,
+    /// Debug is on and still has to be turned off.
+    debugging: bool,
+    /// PID of the remote watchdog job.
+    watchdog: Option<String>,
+}
+
+impl<'a> CaptureSession<'a> {
+    pub fn start(
+        runner: &'a CommandRunner,
+        process: ProcessInfo,
+    ) -> TraceResult<Self> {
+        // Rather than find out only when fetching the log
+        runner.module_type(&process)?;
+        let mut session = Self {
+            runner,
+            process,
+            clock: RemoteClock::default(),
+            debugging: false,
+            watchdog: None,
+        };
+        if session.process.instance.is_some() {
+            runner.enable_debug(&session.process)?;
+            session.debugging = true;
+        }
+
+        // The log is fetched from the remote time to the nearest
+        // minute. Debug is turned off by `drop` should this fail.
+        session.clock = runner.get_remote_clock()?;
+        Ok(session)
+    }
+
+    pub fn process(&self) -> &ProcessInfo {
+        &self.process
+    }
+
+    /// Remote time (`HH:MM`) the capture started at.
+    pub fn start_time(&self) -> &str {
+        &self.clock.time
+    }
+
+    /// Remote clock when the capture started.
+    pub fn start_clock(&self) -> &RemoteClock {
+        &self.clock
+    }
+
+    /// Have the host turn debug off by itself `after` from now, should
+    /// this session never get to.
+    pub fn watchdog(&mut self, after: Duration) -> TraceResult<()> {
+        if self.debugging {
+            let pid =
+                self.runner.schedule_disable_debug(&self.process, after)?;
+            self.watchdog = Some(pid);
+        }
+        Ok(())
+    }
+
+    /// Current size in bytes of the traced log.
+    pub fn log_size(&self) -> TraceResult<u64> {
+        self.runner.log_size(&self.process)
+    }
+
+    /// `len` bytes of the traced log, starting `offset` bytes in.
+    pub fn read_log(&self, offset: u64, len: u64) -> TraceResult<String> {
+        self.runner.read_log(&self.process, offset, len)
+    }
+
+    /// Fetch the log written since `start` and disable debug, even
+    /// when the fetch fails.
+    pub fn finish(mut self) -> TraceResult<String> {
+        // Tail the trace file only from the moment we started the test
+        let trace = self.runner.get_trace(&self.process, &self.clock.time);
+        let disabled = self.disable_debug();
+        let trace = trace?;
+        disabled?;
+        Ok(trace)
+    }
+
+    /// Disable debug and keep `log`, followed with `read_log` from the
+    /// start of the capture, rather than fetching the log again.
+    pub fn finish_followed(mut self, log: String) -> TraceResult<String> {
+        self.disable_debug()?;
+        Ok(log)
+    }
+
+    fn disable_debug(&mut self) -> TraceResult<()> {
+        if !self.debugging {
+            return Ok(());
+        }
+        self.runner.disable_debug(&self.process)?;
+        self.debugging = false;
+        if let Some(pid) = self.watchdog.take() {
+            // Debug is off already, at worst it gets turned off twice
+            let _ = self.runner.cancel_watchdog(&pid);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CaptureSession<'_> {
+    fn drop(&mut self) {
+        // Nothing to report the error to, the watchdog is the fallback
+        let _ = self.disable_debug();
+    }
+}
@@ -1,10 +1,22 @@
-use crate::error::TraceResult;
+use crate::error::{Error, TraceResult};
+use crate::executor::RemoteExecutor;
+use crate::modules::{ModuleType, ModuleTypes};
+use crate::preflight::HostHealth;
 use crate::ssh::SshClient;
+use crate::status::StatusReport;
 use regex::Regex;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
 
-const CIRPACK_PROCESSES: &[&str] = &[
+/// Same columns as `ps aux` but with the elapsed time in place of
+/// START so the status report can show process uptime.
+const STATUS_PS_FORMAT: &str =
+    "user,pid,pcpu,pmem,vsz,rss,tty,stat,etime,time,args";
+
+/// Processes listed by `status` unless told otherwise.
+pub const CIRPACK_PROCESSES: &[&str] = &[
     "ibcf",
     "bgcf",
     "gw_sip",
@@ -19,41 +31,208 @@ const CIRPACK_PROCESSES: &[&str] = &[
     "ipc2netgrouper",
 ];
 
-pub struct CommandRunner(RefCell<SshClient>);
+/// Separates the outputs of the commands batched by `section_script`.
+const SECTION_MARKER: &str = "@@cirtrace:";
+
+pub struct CommandRunner {
+    executor: RefCell<Box<dyn RemoteExecutor>>,
+    modules: ModuleTypes,
+}
+
+/// A single Cirpack process as listed by `ps aux`.
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub owner: String,
+    pub pid: String,
+    /// %MEM column.
+    pub mem_percent: String,
+    /// Resident memory in kB.
+    pub rss: u64,
+    /// START column for `ps aux`, elapsed time for the status listing.
+    pub start: String,
+    /// Binary name, i.e. `ibcf` or `bgcf`.
+    pub process: String,
+    /// Value of the `-i` argument.
+    pub instance: Option<String>,
+    /// Value of the `-r` argument, i.e. `ibcf_core`.
+    pub process_name: Option<String>,
+    /// Value of the `-f` argument.
+    pub config_file: Option<String>,
+}
+
+impl std::fmt::Display for ProcessInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -i{} {} [{}] (pid {})",
+            self.process,
+            self.instance.as_deref().unwrap_or("?"),
+            self.process_name.as_deref().unwrap_or("n/a"),
+            self.config_file.as_deref().unwrap_or("no config"),
+            self.pid
+        )
+    }
+}
+
+/// The clock of the remote host at one moment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteClock {
+    /// Time of day to the minute, `HH:MM`, as the log shows it.
+    pub time: String,
+    pub unix_time: Option<u64>,
+    /// Offset from UTC in seconds, 3600 for `+0100`.
+    pub utc_offset: Option<i32>,
+}
+
+impl ProcessInfo {
+    fn render(&self, template: &str) -> String {
+        ModuleType::render(
+            template,
+            &self.process,
+            self.instance.as_deref(),
+            self.process_name.as_deref(),
+        )
+    }
+}
 
 impl CommandRunner {
     pub fn new<S>(host: S, username: S, password: S) -> TraceResult<Self>
     where
         S: Into<String>,
     {
-        let host = host.into().parse().expect("Failed to parse the IP address");
+        let host = host.into();
+        let host = host.parse().map_err(|_| Error::InvalidHost(host))?;
         let socket = SocketAddr::new(IpAddr::V4(host), 22);
 
         let client =
             SshClient::connect(socket, &username.into(), &password.into())?;
-        Ok(Self(RefCell::new(client)))
+        Ok(Self::with_executor(client))
+    }
+
+    /// Run the commands through something other than SSH, i.e. a
+    /// `LocalExecutor` or a `ScriptedExecutor` in tests.
+    pub fn with_executor<E>(executor: E) -> Self
+    where
+        E: RemoteExecutor + 'static,
+    {
+        Self {
+            executor: RefCell::new(Box::new(executor)),
+            modules: ModuleTypes::default(),
+        }
+    }
+
+    /// Drive debug with `modules` rather than the built-in types only.
+    pub fn module_types(mut self, modules: ModuleTypes) -> Self {
+        self.modules = modules;
+        self
     }
-    pub fn enable_debug(&self, name: &str, instance: &str) -> TraceResult<()> {
-        let _ = self.0.borrow_mut().send_cmd(&format!(
-            "mgt_cscf -name={} -i{} -debug=3 -loglevel=0",
-            name, instance,
-        ))?;
+
+    /// How debug is driven on `p`.
+    pub fn module_type(&self, p: &ProcessInfo) -> TraceResult<&ModuleType> {
+        self.modules
+            .find(&p.process)
+            .ok_or_else(|| Error::UnsupportedModule(p.process.clone()))
+    }
+
+    pub fn enable_debug(&self, p: &ProcessInfo) -> TraceResult<()> {
+        self.run_checked(&p.render(&self.module_type(p)?.enable_debug))?;
         Ok(())
     }
 
-    pub fn disable_debug(&self, name: &str, instance: &str) -> TraceResult<()> {
-        let _ = self.0.borrow_mut().send_cmd(&format!(
-            "mgt_cscf -name={} -i{} -debug=0 -loglevel=1",
-            name, instance,
-        ))?;
+    pub fn disable_debug(&self, p: &ProcessInfo) -> TraceResult<()> {
+        self.run_checked(&p.render(&self.module_type(p)?.disable_debug))?;
         Ok(())
     }
 
+    /// Run `cmd`, failing unless it exits with 0. The output includes
+    /// stderr so that the error says why.
+    fn run_checked(&self, cmd: &str) -> TraceResult<String> {
+        let marker = format!("{}exit ", SECTION_MARKER);
+        let out = self
+            .executor
+            .borrow_mut()
+            .send_cmd(&format!("{} 2>&1; echo {}$?", cmd, marker))?;
+        let (output, status) = out.rsplit_once(&marker).unwrap_or((&out, ""));
+        match status.trim() {
+            "0" => Ok(output.to_owned()),
+            status => Err(Error::CommandFailed(
+                cmd.to_owned(),
+                status.to_owned(),
+                output.trim().to_owned(),
+            )),
+        }
+    }
+
+    /// Leave a background job on the host that disables debug on `p`
+    /// `after` from now, even if the SSH session is gone by then.
+    /// Returns its PID, for [`CommandRunner::cancel_watchdog`].
+    pub fn schedule_disable_debug(
+        &self,
+        p: &ProcessInfo,
+        after: Duration,
+    ) -> TraceResult<String> {
+        let disable = p.render(&self.module_type(p)?.disable_debug);
+        let job = format!("sleep {}; {}", after.as_secs(), disable);
+        let cmd = format!(
+            "nohup sh -c {} >/dev/null 2>&1 & echo $!",
+            shell_quote(&job)
+        );
+        let pid = self.executor.borrow_mut().send_cmd(&cmd)?;
+        let pid = pid.trim();
+        if pid.is_empty() || !pid.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Error::UnexpectedOutput(cmd, pid.to_owned()));
+        }
+        Ok(pid.to_owned())
+    }
+
+    /// Stop a job left by [`CommandRunner::schedule_disable_debug`].
+    pub fn cancel_watchdog(&self, pid: &str) -> TraceResult<()> {
+        let _ = self
+            .executor
+            .borrow_mut()
+            .send_cmd(&format!("kill {}", pid))?;
+        Ok(())
+    }
+
+    /// List every Cirpack process whose `ps` line contains `term`.
+    pub fn find_processes(&self, term: &str) -> TraceResult<Vec<ProcessInfo>> {
+        let ps_out = self.get_ps_list(&[term])?;
+
+        Ok(ps_out
+            .lines()
+            .filter(|l| !l.contains("grep"))
+            .filter_map(parse_ps_line)
+            .collect())
+    }
+
+    /// Time of day, Unix time and UTC offset of the remote host, in a
+    /// single read of its clock.
+    pub fn get_remote_clock(&self) -> TraceResult<RemoteClock> {
+        let out = self
+            .executor
+            .borrow_mut()
+            .send_cmd("date \"+%H:%M %s %z\"")?;
+        let mut fields = out.split_whitespace();
+        Ok(RemoteClock {
+            time: fields.next().unwrap_or_default().to_owned(),
+            unix_time: fields.next().and_then(|s| s.parse().ok()),
+            utc_offset: fields.next().and_then(parse_utc_offset),
+        })
+    }
+
     pub fn get_remote_time(&self) -> TraceResult<String> {
-        let remote_time = self.0.borrow_mut().send_cmd("date \"+%H:%M\"")?;
+        let remote_time =
+            self.executor.borrow_mut().send_cmd("date \"+%H:%M\"")?;
         Ok(remote_time.trim().to_owned())
     }
 
+    /// Contents of a file on the remote host.
+    pub fn read_file(&self, path: &str) -> TraceResult<String> {
+        self.executor
+            .borrow_mut()
+            .send_cmd(&format!("cat {}", shell_quote(path)))
+    }
+
     pub fn get_ps_list(&self, greps: &[&str]) -> TraceResult<String> {
         let mut ps_command = String::from("ps aux |");
 
@@ -63,114 +242,191 @@ impl CommandRunner {
         // remove last "|"
         ps_command.pop();
 
-        Ok(self.0.borrow_mut().send_cmd(&ps_command)?)
+        self.executor.borrow_mut().send_cmd(&ps_command)
     }
 
     pub fn get_trace(
         &self,
-        process_name: &str,
+        p: &ProcessInfo,
         start_time: &str,
     ) -> TraceResult<String> {
-        Ok(self.0.borrow_mut().send_cmd(
-        &format!("tail -n +$(grep -m 1 -n {1} /home/log/{0}.1 | cut -d':' -f 1) /home/log/{0}.1", process_name, start_time))?)
-    }
-
-    pub fn show_status(&self) -> TraceResult<()> {
-        println!("Gathering info....");
-        //let ctrl = self.0.borrow_mut().send_cmd("ctrl")?;
-        //println!("ctrl: {}", ctrl);
-
-        let hostname = self.0.borrow_mut().send_cmd("uname -n")?;
-
-        let processes: Vec<_> = CIRPACK_PROCESSES
-            .iter()
-            .map(|proc| {
-                (
-                    proc,
-                    self.0
-                        .borrow_mut()
-                        .send_cmd(&format!("ps aux | grep {}", proc)),
-                )
-            })
-            .filter(|(_, r)| r.is_ok()) // get only successes
-            .map(|(p, r)| (p, r.unwrap())) // unpack the results
-            .collect();
-
-        println!("{:_>1$}", "_", 5 * 15 + 6);
-        println!(
-            "|{3:^0$} {1:^0$} {3:^0$} {2:^0$} {3:^0$}|",
-            15,
-            "Hostname:",
-            hostname.trim(),
-            ""
-        );
-        println!("{:_>1$}", "_", 5 * 15 + 6);
-        println!(
-            "|{1:^0$}|{2:^0$}|{3:^0$}|{4:^0$}|{5:^0$}|",
-            15,
-            "Process owner",
-            "Process #",
-            "Process name",
-            "Instance",
-            "Instance name"
-        );
-        for (_n, s) in processes.iter().map(|(&p, r)| {
-            (
-                p,
-                r.split('\n')
-                    .filter(|e| !e.contains("grep"))
-                    .collect::<Vec<_>>(),
-            )
-        }) {
-            for ss in s.iter().filter(|p| !p.is_empty()) {
-                let p = parse_ps_line(ss);
-                println!(
-                    "|{1:^0$}|{2:^0$}|{3:^0$}|{4:^0$}|{5:^0$}|",
-                    15, p.0, p.1, p.2, p.3, p.4
-                );
+        let log_file = p.render(&self.module_type(p)?.log_file);
+        self.executor.borrow_mut().send_cmd(&format!(
+            "tail -n +$(grep -m 1 -n {1} {0} | cut -d':' -f 1) {0}",
+            log_file, start_time
+        ))
+    }
+
+    /// Size in bytes of the log file of `p`.
+    pub fn log_size(&self, p: &ProcessInfo) -> TraceResult<u64> {
+        let log_file = p.render(&self.module_type(p)?.log_file);
+        let cmd = format!("stat -c %s {}", log_file);
+        let size = self.executor.borrow_mut().send_cmd(&cmd)?;
+        size.trim()
+            .parse()
+            .map_err(|_| Error::UnexpectedOutput(cmd, size.trim().to_owned()))
+    }
+
+    /// `len` bytes of the log file of `p`, starting `offset` bytes in.
+    pub fn read_log(
+        &self,
+        p: &ProcessInfo,
+        offset: u64,
+        len: u64,
+    ) -> TraceResult<String> {
+        let log_file = p.render(&self.module_type(p)?.log_file);
+        self.executor.borrow_mut().send_cmd(&format!(
+            "tail -c +{} {} | head -c {}",
+            offset + 1,
+            log_file,
+            len
+        ))
+    }
+
+    /// Load and `/home/log` usage, to check before enabling debug.
+    pub fn host_health(&self) -> TraceResult<HostHealth> {
+        let probes =
+            self.executor.borrow_mut().send_cmd(&section_script(&[
+                ("proc", "cat /proc/uptime /proc/loadavg"),
+                ("nproc", "nproc"),
+                ("df", "df -Pk /home/log"),
+            ]))?;
+        let sections = split_sections(&probes);
+        let section = |name| sections.get(name).copied().unwrap_or("");
+
+        // Same probes as the status report
+        let mut report = StatusReport::new(String::new());
+        report.parse_proc_stats(section("proc"));
+        report.parse_disk_usage(section("df"));
+        Ok(HostHealth {
+            load: report.load,
+            cpus: section("nproc").trim().parse().ok(),
+            log_disk: report.log_disk,
+        })
+    }
+
+    /// Collect everything the `status` subcommand reports on.
+    ///
+    /// All the probes run as a single remote script.
+    pub fn status_report(
+        &self,
+        processes: &[&str],
+    ) -> TraceResult<StatusReport> {
+        let probes =
+            self.executor.borrow_mut().send_cmd(&section_script(&[
+                ("hostname", "uname -n".to_owned()),
+                ("proc", "cat /proc/uptime /proc/loadavg".to_owned()),
+                ("df", "df -Pk /home/log".to_owned()),
+                ("ps", format!("ps -eo {}", STATUS_PS_FORMAT)),
+            ]))?;
+        let sections = split_sections(&probes);
+        let section = |name| sections.get(name).copied().unwrap_or("");
+
+        let mut report =
+            StatusReport::new(section("hostname").trim().to_owned());
+        report.parse_proc_stats(section("proc"));
+        report.parse_disk_usage(section("df"));
+
+        for line in section("ps").lines() {
+            match parse_ps_line(line) {
+                Some(p) if processes.contains(&p.process.as_str()) => {
+                    report.add_process(p)
+                }
+                Some(_) => {}
+                None if processes
+                    .iter()
+                    .any(|p| line.contains(&format!("/bin/{}", p))) =>
+                {
+                    report
+                        .warn(format!("Skipping unparsable ps line: {}", line))
+                }
+                None => {}
             }
         }
 
-        println!("{:_>1$}", "_", 5 * 15 + 6);
-        Ok(())
+        report.run_checks();
+        Ok(report)
     }
 }
 
-fn parse_ps_line(line: &str) -> (String, String, String, String, String) {
+/// `+0100` or `-0330` as seconds east of UTC.
+fn parse_utc_offset(s: &str) -> Option<i32> {
+    let (sign, hhmm) = match (s.strip_prefix('+'), s.strip_prefix('-')) {
+        (Some(hhmm), _) => (1, hhmm),
+        (_, Some(hhmm)) => (-1, hhmm),
+        _ => return None,
+    };
+    if hhmm.len() != 4 || !hhmm.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = hhmm[..2].parse().ok()?;
+    let minutes: i32 = hhmm[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// `s` as a single shell word.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Build a shell script running every command in turn, each output
+/// preceded by a `SECTION_MARKER` line naming it.
+fn section_script<S: AsRef<str>>(probes: &[(&str, S)]) -> String {
+    probes
+        .iter()
+        .map(|(name, cmd)| {
+            format!("echo '{}{}'; {} 2>&1", SECTION_MARKER, name, cmd.as_ref())
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Split the output of a `section_script` back into named sections.
+fn split_sections(s: &str) -> HashMap<&str, &str> {
+    let mut sections = HashMap::new();
+
+    for chunk in s.split(SECTION_MARKER).skip(1) {
+        let (name, body) = chunk.split_once('\n').unwrap_or((chunk, ""));
+        sections.insert(name.trim(), body);
+    }
+    sections
+}
+
+/// Parse a `ps aux` line of a Cirpack process, i.e.:
+/// omni 28848 0.0 8.6 770804 714432 ? Sl 2018 0:50 /home/omni/bin/ibcf -i1 -ribcf_core -f/home/etc/ibcf_core.cfg -tpip=254
+///
+/// Returns `None` for anything that isn't a binary started from
+/// `/home/<user>/bin`.
+fn parse_ps_line(line: &str) -> Option<ProcessInfo> {
     lazy_static! {
-        static ref PROCESS_DETAILS: Regex = Regex::new(r"^(?P<process_owner>[[:alpha:]]*)\s*(?P<process_number>[[:digit:]]{1,5}).*.home.[[:alpha:]]*.bin.(?P<process_name>[[:alnum:],_,-]*)\s*(?:-i(?P<instance_number>[[:digit:]]*))*\s*(?:-r(?P<instance_name>[[:alpha:],_,-]*))?.*$").unwrap();
+        static ref PROCESS_DETAILS: Regex = Regex::new(r"^(?P<process_owner>\S+)\s+(?P<process_number>[[:digit:]]+)\s+\S+\s+(?P<mem>\S+)\s+\S+\s+(?P<rss>[[:digit:]]+)\s+\S+\s+\S+\s+(?P<start>\S+)\s+\S+\s+/home/[^/\s]+/bin/(?P<process_name>[[:alnum:]_-]+)(?P<args>.*)$").unwrap();
+        static ref PROCESS_ARG: Regex =
+            Regex::new(r"\s-(?P<flag>[irf])(?P<value>\S+)").unwrap();
     }
 
-    let captures = PROCESS_DETAILS
-        .captures(line)
-        .expect("Captures unavailable");
+    let captures = PROCESS_DETAILS.captures(line.trim())?;
 
-    let process_owner = match captures.name("process_owner") {
-        Some(c) => c.as_str().to_owned(),
-        None => "n/a".to_owned(),
-    };
-    let process_number = match captures.name("process_number") {
-        Some(c) => c.as_str().to_owned(),
-        None => "n/a".to_owned(),
-    };
-    let process_name = match captures.name("process_name") {
-        Some(c) => c.as_str().to_owned(),
-        None => "n/a".to_owned(),
-    };
-    let instance_number = match captures.name("instance_number") {
-        Some(c) => c.as_str().to_owned(),
-        None => "n/a".to_owned(),
-    };
-    let instance_name = match captures.name("instance_name") {
-        Some(c) => c.as_str().to_owned(),
-        None => "n/a".to_owned(),
+    let mut info = ProcessInfo {
+        owner: captures["process_owner"].to_owned(),
+        pid: captures["process_number"].to_owned(),
+        mem_percent: captures["mem"].to_owned(),
+        rss: captures["rss"].parse().ok()?,
+        start: captures["start"].to_owned(),
+        process: captures["process_name"].to_owned(),
+        instance: None,
+        process_name: None,
+        config_file: None,
     };
 
-    (
-        process_owner,
-        process_number,
-        process_name,
-        instance_number,
-        instance_name,
-    )
+    for arg in PROCESS_ARG.captures_iter(&captures["args"]) {
+        let value = Some(arg["value"].to_owned());
+        match &arg["flag"] {
+            "i" => info.instance = value,
+            "r" => info.process_name = value,
+            "f" => info.config_file = value,
+            _ => {}
+        }
+    }
+
+    Some(info)
 }
@@ -0,0 +1,73 @@
+//! Phone number normalisation.
+//!
+//! The same number shows up as `+353871234567`, `00353871234567`,
+//! `0871234567`, `353871234567` or `tel:+353-87-123-4567` depending on
+//! which side of the SBC logged it.
+
+/// `number` as `+<country code><national number>`, given the country
+/// code of the national (`0...`) numbers. Numbers without a prefix
+/// that don't start with the country code are local and returned as
+/// bare digits. `None` when it isn't a phone number at all.
+pub fn normalize(number: &str, country_code: &str) -> Option<String> {
+    let number = number.trim();
+    let number = number.strip_prefix("tel:").unwrap_or(number);
+    let number = number.split(';').next()?;
+
+    // Visual separators (RFC 3966)
+    let digits: String = number
+        .chars()
+        .filter(|c| !matches!(c, '-' | '.' | '(' | ')' | ' '))
+        .collect();
+    let (international, digits) = match digits.strip_prefix('+') {
+        Some(d) => (true, d),
+        None => (false, digits.as_str()),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(if international {
+        format!("+{}", digits)
+    } else if let Some(rest) = digits.strip_prefix("00") {
+        format!("+{}", rest)
+    } else if let Some(rest) = digits.strip_prefix('0') {
+        format!("+{}{}", country_code, rest)
+    } else if digits.starts_with(country_code)
+        && digits.len() >= country_code.len() + MIN_NATIONAL_DIGITS
+    {
+        format!("+{}", digits)
+    } else {
+        digits.to_owned()
+    })
+}
+
+/// Shortest national number we take an unprefixed number starting
+/// with the country code to be.
+const MIN_NATIONAL_DIGITS: usize = 7;
+
+/// Fewest digits of a search term taken to be a phone number.
+const MIN_TERM_DIGITS: usize = 6;
+
+/// Whether search term `term` looks like a phone number: an optional
+/// `+`, then at least six digits and visual separators. IPv4 addresses
+/// and their prefixes (`192.168.100`) aren't.
+pub fn is_phone_number(term: &str) -> bool {
+    let term = term.trim();
+    let rest = term.strip_prefix('+').unwrap_or(term);
+    let digits = rest.chars().filter(|c| c.is_ascii_digit()).count();
+    let dotted_quad = rest.contains('.')
+        && rest
+            .split('.')
+            .all(|g| (1..=3).contains(&g.len()) && g.parse::<u8>().is_ok());
+    rest.chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '-' | '.' | '(' | ')' | ' '))
+        && digits >= MIN_TERM_DIGITS
+        && !dotted_quad
+}
+
+/// Whether `s` is a valid country calling code.
+pub fn is_country_code(s: &str) -> bool {
+    (1..=3).contains(&s.len())
+        && s.chars().all(|c| c.is_ascii_digit())
+        && !s.starts_with('0')
+}
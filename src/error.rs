@@ -6,8 +6,27 @@ pub enum Error {
     SshHandshake(ssh2::Error),
     SshAuthentication(ssh2::Error),
     Command(ssh2::Error, String),
+    CommandFailed(String, String, String),
     File(std::io::Error, std::path::PathBuf),
     Write(std::io::Error),
+    ModuleNotFound(String),
+    AmbiguousModule(Vec<String>),
+    SelectionAborted,
+    Prompt(std::io::Error),
+    Serialize(serde_json::Error),
+    UnsupportedModule(String),
+    InvalidModuleTypes(serde_json::Error, std::path::PathBuf),
+    InvalidHost(String),
+    LocalCommand(std::io::Error, String),
+    UnexpectedCommand(String),
+    UnexpectedOutput(String, String),
+    InvalidFilter(String),
+    InvalidTime(String),
+    InvalidHepUrl(String),
+    Hep(std::io::Error),
+    OutputExists(std::path::PathBuf),
+    UnsafeToTrace(Vec<String>),
+    Interrupted,
 }
 
 pub type TraceResult<T, E = Error> = std::result::Result<T, E>;
@@ -33,6 +52,13 @@ impl std::fmt::Display for Error {
             Error::Command(e, cmd) => {
                 write!(f, "Failed to execute command: {}. Error: {}", cmd, e)
             }
+            Error::CommandFailed(cmd, status, out) => write!(
+                f,
+                "Command failed with exit status {}: {}\n{}",
+                if status.is_empty() { "unknown" } else { status },
+                cmd,
+                out
+            ),
             Error::File(e, path) => write!(
                 f,
                 "Failed to open file [{}]. Error: {}",
@@ -42,6 +68,80 @@ impl std::fmt::Display for Error {
             Error::Write(e) => {
                 write!(f, "Failed to write to file. Error: {}", e)
             }
+            Error::ModuleNotFound(s) => {
+                write!(f, "No running process matches: {}", s)
+            }
+            Error::AmbiguousModule(candidates) => {
+                writeln!(
+                    f,
+                    "Multiple processes match, narrow it down with -M or -i:"
+                )?;
+                for c in candidates.iter() {
+                    writeln!(f, "  {}", c)?;
+                }
+                Ok(())
+            }
+            Error::SelectionAborted => write!(f, "No process selected"),
+            Error::Prompt(e) => {
+                write!(f, "Failed to read selection. Error: {}", e)
+            }
+            Error::LocalCommand(e, cmd) => {
+                write!(f, "Failed to execute command: {}. Error: {}", cmd, e)
+            }
+            Error::UnexpectedCommand(cmd) => {
+                write!(f, "No recorded output for command: {}", cmd)
+            }
+            Error::UnexpectedOutput(cmd, out) => {
+                write!(f, "Unexpected output from {}: {}", cmd, out)
+            }
+            Error::InvalidFilter(e) => {
+                write!(f, "Invalid filter expression: {}", e)
+            }
+            Error::InvalidTime(t) => write!(
+                f,
+                "Invalid time: {}. Expected HH:MM[:SS[.mmm]] or +<offset>, \
+                 i.e. +90s or +1m30s",
+                t
+            ),
+            Error::InvalidHepUrl(url) => write!(
+                f,
+                "Invalid HEP destination: {}. Expected udp://host[:port]",
+                url
+            ),
+            Error::Hep(e) => {
+                write!(f, "Failed to send to the HEP server. Error: {}", e)
+            }
+            Error::OutputExists(path) => write!(
+                f,
+                "{} already exists. Use --overwrite or --append",
+                path.display()
+            ),
+            Error::UnsafeToTrace(problems) => {
+                writeln!(f, "Not enabling debug:")?;
+                for p in problems.iter() {
+                    writeln!(f, "  {}", p)?;
+                }
+                write!(f, "Use --force to trace anyway")
+            }
+            Error::Interrupted => write!(f, "Interrupted, debug disabled"),
+            Error::InvalidHost(h) => {
+                write!(f, "Failed to parse the IP address: {}", h)
+            }
+            Error::UnsupportedModule(m) => write!(
+                f,
+                "Don't know how to enable debug on {}. Describe it in a \
+                 --module-types file",
+                m
+            ),
+            Error::InvalidModuleTypes(e, path) => write!(
+                f,
+                "Invalid module types in [{}]. Error: {}",
+                path.display(),
+                e
+            ),
+            Error::Serialize(e) => {
+                write!(f, "Failed to serialize output. Error: {}", e)
+            }
         }
     }
 }
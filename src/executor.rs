@@ -0,0 +1,72 @@
+use crate::error::{Error, TraceResult};
+use std::{cell::RefCell, process::Command, rc::Rc};
+
+/// Something that can run a shell command on the traced host.
+pub trait RemoteExecutor {
+    /// Run `command` and return everything it wrote to stdout.
+    fn send_cmd(&mut self, command: &str) -> TraceResult<String>;
+}
+
+/// Runs the commands with `sh -c` on the local machine.
+///
+/// Handy when cirtrace runs on the SBC itself.
+#[derive(Debug, Default)]
+pub struct LocalExecutor;
+
+impl RemoteExecutor for LocalExecutor {
+    fn send_cmd(&mut self, command: &str) -> TraceResult<String> {
+        let out = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .map_err(|e| Error::LocalCommand(e, command.to_owned()))?;
+
+        Ok(String::from_utf8_lossy(&out.stdout).to_string())
+    }
+}
+
+/// Replays canned outputs instead of running anything.
+///
+/// Each command is answered with the output of the first recorded
+/// pair whose command is contained in it, so a pair can be recorded
+/// against just the stable part of a command line. Every command
+/// sent is kept in `history` for later inspection.
+#[derive(Debug, Default)]
+pub struct ScriptedExecutor {
+    responses: Vec<(String, String)>,
+    history: Rc<RefCell<Vec<String>>>,
+}
+
+impl ScriptedExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer commands containing `command` with `output`.
+    pub fn on<C, O>(mut self, command: C, output: O) -> Self
+    where
+        C: Into<String>,
+        O: Into<String>,
+    {
+        self.responses.push((command.into(), output.into()));
+        self
+    }
+
+    /// Handle to the commands sent so far, usable after the executor
+    /// has been handed over to a `CommandRunner`.
+    pub fn history(&self) -> Rc<RefCell<Vec<String>>> {
+        Rc::clone(&self.history)
+    }
+}
+
+impl RemoteExecutor for ScriptedExecutor {
+    fn send_cmd(&mut self, command: &str) -> TraceResult<String> {
+        self.history.borrow_mut().push(command.to_owned());
+
+        self.responses
+            .iter()
+            .find(|(c, _)| command.contains(c.as_str()))
+            .map(|(_, out)| out.clone())
+            .ok_or_else(|| Error::UnexpectedCommand(command.to_owned()))
+    }
+}
@@ -0,0 +1,568 @@
+//! Filter expressions selecting the calls to print.
+//!
+//! ```text
+//! from.user=3531234*
+//! to.host=10.1.1.1
+//! status>=400
+//! method=INVITE and header[P-Asserted-Identity]~"^sip:\+353"
+//! not (method=OPTIONS or method=REGISTER)
+//! ```
+//!
+//! `=` and `!=` compare case insensitively and allow `*` and `?`
+//! wildcards, `~` and `!~` match a regex and `<`, `<=`, `>`, `>=`
+//! compare numbers. A word that isn't a comparison is a plain search
+//! term: it matches an INVITE with a line containing it. A `-S` term
+//! not starting with a field name is a plain term as a whole.
+//!
+//! [`Filter::regex`] matches the URIs, Call-ID and ISUP numbers of a
+//! message instead, and [`Filter::with_e164`] makes numbers match in
+//! any of their national and international forms.
+
+use crate::e164;
+use crate::error::{Error, TraceResult};
+use crate::sip_parse::SipPacket;
+use crate::uri::SipUri;
+use regex::Regex;
+
+/// Headers whose URIs carry the calling, called or redirecting number.
+const NUMBER_HEADERS: &[&str] = &[
+    "From",
+    "To",
+    "Contact",
+    "P-Asserted-Identity",
+    "P-Preferred-Identity",
+    "Remote-Party-ID",
+    "Diversion",
+];
+
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// Matches every message.
+    All,
+    /// INVITE with a line containing the term.
+    Term(String),
+    Compare(Field, Op, Value),
+    /// Message with a URI user or host, Call-ID or ISUP number
+    /// matching the regex. Numbers are tried in E.164 form too when
+    /// the country code is set.
+    Regex(Regex, Option<String>),
+    /// INVITE carrying a number, normalised with the country code,
+    /// that contains this one.
+    Number(String, String),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    Method,
+    Status,
+    CallId,
+    CSeq,
+    From,
+    FromUser,
+    FromHost,
+    To,
+    ToUser,
+    ToHost,
+    RequestUser,
+    RequestHost,
+    Header(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Match,
+    NotMatch,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(String),
+    Regex(Regex),
+    Number(i64),
+}
+
+impl Filter {
+    /// Parse an expression.
+    pub fn parse(expr: &str) -> TraceResult<Self> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let filter = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(filter),
+            Some(t) => Err(invalid(format!("unexpected {}", t))),
+        }
+    }
+
+    /// Any of `terms`, each an expression or a plain term. Only terms
+    /// starting with a field name are expressions, so `tag=abc` or
+    /// `<sip:+3531234@10.1.1.1>` are searched for as they are. No
+    /// terms match everything.
+    pub fn parse_terms(terms: &[&str]) -> TraceResult<Self> {
+        let filters = terms
+            .iter()
+            .map(|t| match is_expression(t) {
+                true => Self::parse(t),
+                false => Ok(Filter::Term(t.to_string())),
+            })
+            .collect::<TraceResult<Vec<_>>>()?;
+        Ok(Self::any(filters))
+    }
+
+    /// Any of the plain `terms`, like the `-S` values used to be
+    /// matched.
+    pub fn terms(terms: &[&str]) -> Self {
+        Self::any(terms.iter().map(|t| Filter::Term(t.to_string())).collect())
+    }
+
+    /// A `--search-regex` pattern.
+    pub fn regex(pattern: &str) -> TraceResult<Self> {
+        let re = Regex::new(pattern).map_err(|e| invalid(e.to_string()))?;
+        Ok(Filter::Regex(re, None))
+    }
+
+    /// Match phone numbers in any of their forms, taking national
+    /// numbers to be in `country_code`. Only the terms shaped like a
+    /// phone number are normalised, so `10.1.1.1` or `1234` stay plain
+    /// terms.
+    pub fn with_e164(self, country_code: &str) -> Self {
+        let cc = country_code.to_owned();
+        match self {
+            Filter::Term(t) if e164::is_phone_number(&t) => {
+                match e164::normalize(&t, country_code) {
+                    Some(n) => Filter::Or(
+                        Box::new(Filter::Term(t)),
+                        Box::new(Filter::Number(n, cc)),
+                    ),
+                    None => Filter::Term(t),
+                }
+            }
+            Filter::Regex(re, _) => Filter::Regex(re, Some(cc)),
+            Filter::Not(f) => Filter::Not(Box::new(f.with_e164(country_code))),
+            Filter::And(a, b) => Filter::And(
+                Box::new(a.with_e164(country_code)),
+                Box::new(b.with_e164(country_code)),
+            ),
+            Filter::Or(a, b) => Filter::Or(
+                Box::new(a.with_e164(country_code)),
+                Box::new(b.with_e164(country_code)),
+            ),
+            f => f,
+        }
+    }
+
+    /// Any of `filters`. None match everything.
+    pub fn any(filters: Vec<Filter>) -> Self {
+        filters
+            .into_iter()
+            .reduce(|a, b| Filter::Or(Box::new(a), Box::new(b)))
+            .unwrap_or(Filter::All)
+    }
+
+    /// The plain search terms in the expression, for highlighting.
+    pub fn plain_terms(&self) -> Vec<&str> {
+        match self {
+            Filter::Term(t) => vec![t.as_str()],
+            Filter::Not(f) => f.plain_terms(),
+            Filter::And(a, b) | Filter::Or(a, b) => {
+                let mut terms = a.plain_terms();
+                terms.extend(b.plain_terms());
+                terms
+            }
+            Filter::All
+            | Filter::Compare(..)
+            | Filter::Regex(..)
+            | Filter::Number(..) => Vec::new(),
+        }
+    }
+
+    pub fn matches(&self, p: &SipPacket) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Term(t) => {
+                p.method() == Some("INVITE")
+                    && (p.start_line().contains(t.as_str())
+                        || p.headers().iter().any(|h| h.contains(t.as_str())))
+            }
+            Filter::Compare(field, op, value) => {
+                compare(field.value(p).as_deref(), *op, value)
+            }
+            Filter::Regex(re, cc) => {
+                search_fields(p).iter().any(|f| re.is_match(f))
+                    || cc.as_ref().is_some_and(|cc| {
+                        numbers(p)
+                            .iter()
+                            .filter_map(|n| e164::normalize(n, cc))
+                            .any(|n| re.is_match(&n))
+                    })
+            }
+            Filter::Number(number, cc) => {
+                p.method() == Some("INVITE")
+                    && numbers(p)
+                        .iter()
+                        .filter_map(|n| e164::normalize(n, cc))
+                        .any(|n| n.contains(number.as_str()))
+            }
+            Filter::Not(f) => !f.matches(p),
+            Filter::And(a, b) => a.matches(p) && b.matches(p),
+            Filter::Or(a, b) => a.matches(p) || b.matches(p),
+        }
+    }
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        if let Some(header) = name
+            .strip_prefix("header[")
+            .and_then(|h| h.strip_suffix(']'))
+        {
+            return Some(Field::Header(header.to_owned()));
+        }
+
+        Some(match name.to_ascii_lowercase().as_str() {
+            "method" => Field::Method,
+            "status" => Field::Status,
+            "call-id" | "call_id" | "callid" => Field::CallId,
+            "cseq" => Field::CSeq,
+            "from" => Field::From,
+            "from.user" => Field::FromUser,
+            "from.host" => Field::FromHost,
+            "to" => Field::To,
+            "to.user" => Field::ToUser,
+            "to.host" => Field::ToHost,
+            "ruri.user" => Field::RequestUser,
+            "ruri.host" => Field::RequestHost,
+            _ => return None,
+        })
+    }
+
+    fn value(&self, p: &SipPacket) -> Option<String> {
+        match self {
+            Field::Method => p.method().map(|m| m.to_owned()),
+            Field::Status => p.status_code().map(|s| s.to_string()),
+            Field::CallId => p.call_id().map(|c| c.to_owned()),
+            Field::CSeq => p.cseq().map(|(n, _)| n.to_string()),
+            Field::From => p.header("From").map(|h| h.to_owned()),
+            Field::FromUser => p.from_uri()?.user,
+            Field::FromHost => p.from_uri().map(|u| u.host),
+            Field::To => p.header("To").map(|h| h.to_owned()),
+            Field::ToUser => p.to_uri()?.user,
+            Field::ToHost => p.to_uri().map(|u| u.host),
+            Field::RequestUser => p.request_uri()?.user,
+            Field::RequestHost => p.request_uri().map(|u| u.host),
+            Field::Header(name) => p.header(name).map(|h| h.to_owned()),
+        }
+    }
+}
+
+/// Request-URI and the URIs of the [`NUMBER_HEADERS`].
+fn uris(p: &SipPacket) -> Vec<SipUri> {
+    let headers = NUMBER_HEADERS
+        .iter()
+        .filter_map(|h| SipUri::from_name_addr(p.header(h)?));
+    p.request_uri().into_iter().chain(headers).collect()
+}
+
+/// URI user parts and ISUP called/calling numbers.
+fn numbers(p: &SipPacket) -> Vec<String> {
+    let mut numbers: Vec<_> =
+        uris(p).into_iter().filter_map(|u| u.user).collect();
+    for isup in p
+        .body()
+        .iter()
+        .flat_map(|b| b.parts())
+        .filter_map(|p| p.isup())
+    {
+        numbers.extend(isup.called);
+        numbers.extend(isup.calling);
+    }
+    numbers
+}
+
+/// What a [`Filter::Regex`] is matched against.
+fn search_fields(p: &SipPacket) -> Vec<String> {
+    let mut fields = numbers(p);
+    fields.extend(uris(p).into_iter().map(|u| u.host));
+    fields.extend(p.call_id().map(|c| c.to_owned()));
+    fields
+}
+
+fn compare(actual: Option<&str>, op: Op, expected: &Value) -> bool {
+    let actual = match actual {
+        Some(a) => a,
+        // A missing field is different from anything
+        None => return matches!(op, Op::Ne | Op::NotMatch),
+    };
+
+    match (op, expected) {
+        (Op::Eq, Value::Text(pattern)) => glob_match(pattern, actual),
+        (Op::Ne, Value::Text(pattern)) => !glob_match(pattern, actual),
+        (Op::Match, Value::Regex(re)) => re.is_match(actual),
+        (Op::NotMatch, Value::Regex(re)) => !re.is_match(actual),
+        (op, Value::Number(n)) => match actual.parse::<i64>() {
+            Ok(a) => match op {
+                Op::Lt => a < *n,
+                Op::Le => a <= *n,
+                Op::Gt => a > *n,
+                Op::Ge => a >= *n,
+                _ => false,
+            },
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// Case insensitive match with `*` for any run of characters and `?`
+/// for any single one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.to_lowercase().chars().collect();
+    let t: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut pi, mut ti) = (0, 0);
+    // Position of the last `*` and of the text it was tried against
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            // Let the `*` swallow one more character
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Whether `term` starts with a field name, after any `not` or `(`.
+fn is_expression(term: &str) -> bool {
+    let mut rest = term.trim_start();
+    loop {
+        if let Some(r) = rest.strip_prefix('(') {
+            rest = r.trim_start();
+            continue;
+        }
+        match rest.split_once(char::is_whitespace) {
+            Some((w, r)) if w.eq_ignore_ascii_case("not") => {
+                rest = r.trim_start()
+            }
+            _ => break,
+        }
+    }
+    let name = rest
+        .split(|c: char| c.is_whitespace() || "()\"=!~<>".contains(c))
+        .next()
+        .unwrap_or("");
+    Field::parse(name).is_some()
+}
+
+fn invalid(msg: String) -> Error {
+    Error::InvalidFilter(msg)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Op(Op),
+    Word(String),
+    Quoted(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::Op(op) => write!(f, "operator {:?}", op),
+            Token::Word(w) => write!(f, "'{}'", w),
+            Token::Quoted(q) => write!(f, "\"{}\"", q),
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> TraceResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Only quotes are escaped, backslashes are
+                        // left for regexes
+                        Some('\\') if chars.peek() == Some(&'"') => {
+                            s.push('"');
+                            chars.next();
+                        }
+                        Some(c) => s.push(c),
+                        None => {
+                            return Err(invalid(format!(
+                                "unterminated string in {}",
+                                expr
+                            )))
+                        }
+                    }
+                }
+                tokens.push(Token::Quoted(s));
+            }
+            '=' | '!' | '~' | '<' | '>' => {
+                chars.next();
+                let equals = chars.peek() == Some(&'=');
+                let (op, two_chars) = match c {
+                    '=' => (Op::Eq, equals),
+                    '!' if equals => (Op::Ne, true),
+                    '!' if chars.peek() == Some(&'~') => (Op::NotMatch, true),
+                    '!' => {
+                        return Err(invalid(format!("stray '!' in {}", expr)))
+                    }
+                    '~' => (Op::Match, false),
+                    '<' if equals => (Op::Le, true),
+                    '<' => (Op::Lt, false),
+                    '>' if equals => (Op::Ge, true),
+                    _ => (Op::Gt, false),
+                };
+                if two_chars {
+                    chars.next();
+                }
+                tokens.push(Token::Op(op));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()\"=!~<>".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> TraceResult<Filter> {
+        let mut left = self.and()?;
+        while self.keyword("or") {
+            left = Filter::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> TraceResult<Filter> {
+        let mut left = self.unary()?;
+        while self.keyword("and") {
+            left = Filter::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> TraceResult<Filter> {
+        if self.keyword("not") {
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+
+        match self.next() {
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err(invalid("missing ')'".to_owned())),
+                }
+            }
+            Some(Token::Word(w)) => match self.peek() {
+                Some(Token::Op(_)) => self.comparison(&w),
+                _ => Ok(Filter::Term(w)),
+            },
+            Some(Token::Quoted(q)) => Ok(Filter::Term(q)),
+            Some(t) => Err(invalid(format!("unexpected {}", t))),
+            None => Err(invalid("unexpected end of expression".to_owned())),
+        }
+    }
+
+    fn comparison(&mut self, name: &str) -> TraceResult<Filter> {
+        let field = Field::parse(name)
+            .ok_or_else(|| invalid(format!("unknown field '{}'", name)))?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => unreachable!("comparison() is only called before an operator"),
+        };
+        let raw = match self.next() {
+            Some(Token::Word(w)) | Some(Token::Quoted(w)) => w,
+            _ => return Err(invalid(format!("missing value after {}", name))),
+        };
+
+        let value = match op {
+            Op::Match | Op::NotMatch => Value::Regex(
+                Regex::new(&raw).map_err(|e| invalid(e.to_string()))?,
+            ),
+            Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+                Value::Number(raw.parse().map_err(|_| {
+                    invalid(format!("'{}' is not a number", raw))
+                })?)
+            }
+            Op::Eq | Op::Ne => Value::Text(raw),
+        };
+
+        Ok(Filter::Compare(field, op, value))
+    }
+}
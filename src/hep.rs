@@ -0,0 +1,197 @@
+//! HEPv3 (Homer encapsulation protocol) sink, so the messages pulled
+//! out of the debug logs show up in a Homer capture server.
+
+use crate::error::{Error, TraceResult};
+use crate::log_entry::{Endpoints, LogEntry};
+use crate::sip_parse::SipPacket;
+use std::{
+    convert::TryFrom,
+    net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub const DEFAULT_PORT: u16 = 9060;
+
+/// Largest HEP packet a UDP datagram over IPv4 can carry.
+const MAX_LEN: usize = 65535 - 20 - 8;
+const HEADER_LEN: usize = 6;
+const CHUNK_HEADER_LEN: usize = 6;
+
+// Chunk types of the generic vendor
+const IP_FAMILY: u16 = 0x0001;
+const IP_PROTOCOL: u16 = 0x0002;
+const IPV4_SRC: u16 = 0x0003;
+const IPV4_DST: u16 = 0x0004;
+const IPV6_SRC: u16 = 0x0005;
+const IPV6_DST: u16 = 0x0006;
+const SRC_PORT: u16 = 0x0007;
+const DST_PORT: u16 = 0x0008;
+const TIMESTAMP_SECS: u16 = 0x0009;
+const TIMESTAMP_MICROS: u16 = 0x000a;
+const PROTOCOL_TYPE: u16 = 0x000b;
+const CAPTURE_ID: u16 = 0x000c;
+const PAYLOAD: u16 = 0x000f;
+const CORRELATION_ID: u16 = 0x0011;
+
+const AF_INET: u8 = 2;
+const AF_INET6: u8 = 10;
+const IPPROTO_UDP: u8 = 17;
+const PROTOCOL_SIP: u8 = 1;
+
+/// Sends SIP messages to a HEP server over UDP.
+#[derive(Debug)]
+pub struct HepSender {
+    socket: UdpSocket,
+    capture_id: u32,
+    /// Unix time of the midnight the capture started after.
+    day_start: Option<u64>,
+}
+
+impl HepSender {
+    /// Connect to `udp://host[:port]`.
+    pub fn connect(url: &str) -> TraceResult<Self> {
+        let target = parse_url(url)?;
+        let local: SocketAddr = match target {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => "[::]:0".parse().unwrap(),
+        };
+        let socket = UdpSocket::bind(local).map_err(Error::Hep)?;
+        socket.connect(target).map_err(Error::Hep)?;
+
+        Ok(Self {
+            socket,
+            capture_id: 0,
+            day_start: None,
+        })
+    }
+
+    /// Capture agent ID to tag the packets with.
+    pub fn capture_id(mut self, id: u32) -> Self {
+        self.capture_id = id;
+        self
+    }
+
+    /// The logs only have the time of day. Date it on the day that
+    /// starts at `day_start` (Unix time) instead of the last time the
+    /// clock read that time of day.
+    pub fn day_start(mut self, day_start: u64) -> Self {
+        self.day_start = Some(day_start);
+        self
+    }
+
+    pub fn send(&self, packet: &SipPacket) -> TraceResult<()> {
+        let timestamp = match packet.time() {
+            Some(t) => t.unix_time(self.day_start),
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+        };
+        let bytes = encode(packet, timestamp, self.capture_id);
+        self.socket.send(&bytes).map(|_| ()).map_err(Error::Hep)
+    }
+
+    /// Send every packet, returning how many were sent.
+    pub fn send_all(&self, packets: &[SipPacket]) -> TraceResult<usize> {
+        packets.iter().try_for_each(|p| self.send(p))?;
+        Ok(packets.len())
+    }
+}
+
+/// Parse `udp://host[:port]`.
+pub fn parse_url(url: &str) -> TraceResult<SocketAddr> {
+    let invalid = || Error::InvalidHepUrl(url.to_owned());
+    let hostport = url.strip_prefix("udp://").ok_or_else(invalid)?;
+    let hostport = hostport.trim_end_matches('/');
+    if hostport.is_empty() {
+        return Err(invalid());
+    }
+
+    if let Ok(addr) = hostport.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    let host = hostport.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DEFAULT_PORT));
+    }
+
+    let addrs = if hostport.contains(':') {
+        hostport.to_socket_addrs()
+    } else {
+        (hostport, DEFAULT_PORT).to_socket_addrs()
+    };
+    addrs.ok().and_then(|mut a| a.next()).ok_or_else(invalid)
+}
+
+/// Encode `packet` as a HEPv3 packet. Addresses missing from the log
+/// entry are sent as `0.0.0.0:0`, and a message too long for a single
+/// datagram is cut short like in pcap files.
+pub fn encode(
+    packet: &SipPacket,
+    timestamp: Duration,
+    capture_id: u32,
+) -> Vec<u8> {
+    let endpoints = packet
+        .log_entry()
+        .map(LogEntry::endpoints)
+        .unwrap_or_default();
+
+    let mut chunks = Vec::new();
+    let (src_port, dst_port) = match endpoints {
+        Endpoints::V6(s, d) => {
+            chunk(&mut chunks, IP_FAMILY, &[AF_INET6]);
+            chunk(&mut chunks, IP_PROTOCOL, &[IPPROTO_UDP]);
+            chunk(&mut chunks, IPV6_SRC, &s.ip().octets());
+            chunk(&mut chunks, IPV6_DST, &d.ip().octets());
+            (s.port(), d.port())
+        }
+        Endpoints::V4(s, d) => {
+            chunk(&mut chunks, IP_FAMILY, &[AF_INET]);
+            chunk(&mut chunks, IP_PROTOCOL, &[IPPROTO_UDP]);
+            chunk(&mut chunks, IPV4_SRC, &s.ip().octets());
+            chunk(&mut chunks, IPV4_DST, &d.ip().octets());
+            (s.port(), d.port())
+        }
+    };
+    chunk(&mut chunks, SRC_PORT, &src_port.to_be_bytes());
+    chunk(&mut chunks, DST_PORT, &dst_port.to_be_bytes());
+    chunk(
+        &mut chunks,
+        TIMESTAMP_SECS,
+        &(timestamp.as_secs() as u32).to_be_bytes(),
+    );
+    chunk(
+        &mut chunks,
+        TIMESTAMP_MICROS,
+        &timestamp.subsec_micros().to_be_bytes(),
+    );
+    chunk(&mut chunks, PROTOCOL_TYPE, &[PROTOCOL_SIP]);
+    chunk(&mut chunks, CAPTURE_ID, &capture_id.to_be_bytes());
+    if let Some(call_id) = packet.call_id() {
+        chunk(&mut chunks, CORRELATION_ID, call_id.as_bytes());
+    }
+    let mut payload = packet.to_bytes();
+    payload.truncate(
+        MAX_LEN.saturating_sub(HEADER_LEN + chunks.len() + CHUNK_HEADER_LEN),
+    );
+    chunk(&mut chunks, PAYLOAD, &payload);
+
+    let len = u16::try_from(HEADER_LEN + chunks.len())
+        .expect("chunks are capped at MAX_LEN");
+    let mut hep = b"HEP3".to_vec();
+    hep.extend_from_slice(&len.to_be_bytes());
+    hep.extend(chunks);
+    hep
+}
+
+/// Append a generic vendor chunk, unless it would take the packet
+/// over `MAX_LEN`.
+fn chunk(out: &mut Vec<u8>, chunk_type: u16, value: &[u8]) {
+    let len = match u16::try_from(CHUNK_HEADER_LEN + value.len()) {
+        Ok(len) if HEADER_LEN + out.len() + len as usize <= MAX_LEN => len,
+        _ => return,
+    };
+    out.extend_from_slice(&0u16.to_be_bytes());
+    out.extend_from_slice(&chunk_type.to_be_bytes());
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(value);
+}
@@ -0,0 +1,238 @@
+//! Just enough of ITU-T Q.763 to summarise the ISUP parts of SIP-I
+//! messages (RFC 3204 `application/isup` bodies).
+
+use std::fmt;
+
+const CALLING_PARTY_NUMBER: u8 = 0x0a;
+
+/// Nature of address indicator of an international number.
+const NAI_INTERNATIONAL: u8 = 0x04;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsupSummary {
+    pub message_type: u8,
+    pub called: Option<String>,
+    pub calling: Option<String>,
+    /// Q.850 cause value of a REL.
+    pub cause: Option<u8>,
+}
+
+impl IsupSummary {
+    pub fn message_name(&self) -> &'static str {
+        match self.message_type {
+            0x01 => "IAM",
+            0x02 => "SAM",
+            0x03 => "INR",
+            0x04 => "INF",
+            0x05 => "COT",
+            0x06 => "ACM",
+            0x07 => "CON",
+            0x08 => "FOT",
+            0x09 => "ANM",
+            0x0c => "REL",
+            0x0d => "SUS",
+            0x0e => "RES",
+            0x10 => "RLC",
+            0x2c => "CPG",
+            0x2d => "USR",
+            0x2f => "CFN",
+            _ => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for IsupSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ISUP {} (0x{:02x})",
+            self.message_name(),
+            self.message_type
+        )?;
+        if let Some(ref called) = self.called {
+            write!(f, " called={}", called)?;
+        }
+        if let Some(ref calling) = self.calling {
+            write!(f, " calling={}", calling)?;
+        }
+        if let Some(cause) = self.cause {
+            write!(f, " cause={}", cause)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode the message type and the interesting parameters of an ISUP
+/// message. Returns `None` when the message is too short to have a
+/// type at all; malformed parameters are just left out.
+pub fn decode(msg: &[u8]) -> Option<IsupSummary> {
+    let mut summary = IsupSummary {
+        message_type: *msg.first()?,
+        called: None,
+        calling: None,
+        cause: None,
+    };
+
+    match summary.message_type {
+        // IAM: NCI, FCI(2), CPC, TMR, then the pointers
+        0x01 => {
+            summary.called = pointed_param(msg, 6).and_then(decode_number);
+            if let Some(opt) = pointed_offset(msg, 7) {
+                summary.calling =
+                    optional_param(msg, opt, CALLING_PARTY_NUMBER)
+                        .and_then(decode_number);
+            }
+        }
+        // REL: cause indicators are the only mandatory variable part
+        0x0c => {
+            summary.cause = pointed_param(msg, 1)
+                .and_then(|c| c.get(1))
+                .map(|c| c & 0x7f);
+        }
+        _ => {}
+    }
+
+    Some(summary)
+}
+
+/// `msg` with the called and calling numbers of an IAM passed through
+/// `f`. Replacements must keep the number of digits, others are left
+/// out.
+pub fn map_numbers<F: FnMut(&str) -> String>(msg: &[u8], mut f: F) -> Vec<u8> {
+    let mut out = msg.to_vec();
+    if msg.first() != Some(&0x01) {
+        return out;
+    }
+
+    let mut params = Vec::new();
+    if let Some(start) = pointed_offset(msg, 6) {
+        if let Some(&len) = msg.get(start) {
+            params.push((start + 1, len as usize));
+        }
+    }
+    if let Some(opt) = pointed_offset(msg, 7) {
+        params.extend(optional_param_at(msg, opt, CALLING_PARTY_NUMBER));
+    }
+
+    for (start, len) in params {
+        let param = match msg.get(start..start + len) {
+            Some(param) => param,
+            None => continue,
+        };
+        let number = match decode_number(param) {
+            Some(number) => number,
+            None => continue,
+        };
+
+        let old: Vec<_> = number.trim_start_matches('+').chars().collect();
+        let new: Vec<_> = f(&number).trim_start_matches('+').chars().collect();
+        if new.len() != old.len() || !new.iter().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        for (i, d) in new.iter().enumerate() {
+            let d = *d as u8 - b'0';
+            let byte = &mut out[start + 2 + i / 2];
+            *byte = if i % 2 == 0 {
+                (*byte & 0xf0) | d
+            } else {
+                (*byte & 0x0f) | (d << 4)
+            };
+        }
+    }
+    out
+}
+
+/// Parse `s` as hex, i.e. `01 00 4a 00` or `01004a00`.
+pub fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    let digits: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Absolute offset a pointer octet at `at` points to.
+fn pointed_offset(msg: &[u8], at: usize) -> Option<usize> {
+    match *msg.get(at)? {
+        0 => None,
+        p => Some(at + p as usize),
+    }
+}
+
+/// Contents of the length prefixed parameter the pointer at `at`
+/// points to.
+fn pointed_param(msg: &[u8], at: usize) -> Option<&[u8]> {
+    let start = pointed_offset(msg, at)?;
+    let len = *msg.get(start)? as usize;
+    msg.get(start + 1..start + 1 + len)
+}
+
+/// Walk the optional part starting at `start` looking for `code`.
+fn optional_param(msg: &[u8], start: usize, code: u8) -> Option<&[u8]> {
+    let (at, len) = optional_param_at(msg, start, code)?;
+    msg.get(at..at + len)
+}
+
+/// Offset and length of the value of optional parameter `code`.
+fn optional_param_at(
+    msg: &[u8],
+    mut start: usize,
+    code: u8,
+) -> Option<(usize, usize)> {
+    loop {
+        let c = *msg.get(start)?;
+        if c == 0 {
+            return None;
+        }
+        let len = *msg.get(start + 1)? as usize;
+        msg.get(start + 2..start + 2 + len)?;
+        if c == code {
+            return Some((start + 2, len));
+        }
+        start += 2 + len;
+    }
+}
+
+/// Decode a called/calling party number parameter into digits,
+/// prefixed with `+` for international numbers.
+fn decode_number(param: &[u8]) -> Option<String> {
+    let odd = param.first()? & 0x80 != 0;
+    let nai = param.first()? & 0x7f;
+    let bcd = param.get(2..).filter(|d| !d.is_empty())?;
+
+    let mut digits: Vec<u8> =
+        bcd.iter().flat_map(|b| vec![b & 0x0f, b >> 4]).collect();
+    // The last high nibble is filler for an odd number of digits
+    if odd {
+        digits.pop();
+    }
+
+    let mut number = String::new();
+    if nai == NAI_INTERNATIONAL {
+        number.push('+');
+    }
+    // 0xf is the end of pulsing signal
+    number.extend(digits.into_iter().take_while(|&d| d != 0x0f).map(bcd_digit));
+    Some(number)
+}
+
+fn bcd_digit(d: u8) -> char {
+    match d {
+        0..=9 => (b'0' + d) as char,
+        0x0b => '*',
+        0x0c => '#',
+        _ => (b'a' + d - 0x0a) as char,
+    }
+}
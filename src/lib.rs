@@ -0,0 +1,103 @@
+//! Tracing VoIP calls on a Cirpack platform.
+//!
+//! The `cirtrace` binary is a thin CLI over this crate. The usual
+//! flow is:
+//!
+//! 1. connect to the SBC with [`CommandRunner::new`],
+//! 2. find the process to trace with [`CommandRunner::find_processes`]
+//!    and [`pick_process`],
+//! 3. wrap the test call in a [`CaptureSession`],
+//! 4. pull the SIP messages out of the log with [`SipParser`].
+//!
+//! ```no_run
+//! use cirtrace::{CaptureSession, CommandRunner, ModuleSelector, SipParser};
+//!
+//! # fn main() -> cirtrace::TraceResult<()> {
+//! let runner = CommandRunner::new("192.168.1.100", "omni", "secret")?;
+//! let selector = ModuleSelector {
+//!     module_name: Some("ibcf_core"),
+//!     ..Default::default()
+//! };
+//! let candidates = runner.find_processes("ibcf_core")?;
+//! let process = cirtrace::pick_process(&selector, candidates, false)?;
+//!
+//! let session = CaptureSession::start(&runner, process)?;
+//! // place the test call here
+//! let log = session.finish()?;
+//!
+//! for packet in SipParser::new().extract_sip(&log, &["3531234"], true) {
+//!     println!("{}", packet);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Commands normally go over SSH. [`CommandRunner::with_executor`]
+//! takes any [`RemoteExecutor`] instead, such as [`LocalExecutor`] when
+//! running on the SBC itself or [`ScriptedExecutor`] to replay
+//! recorded outputs in tests.
+//!
+//! The `status` subcommand is available as
+//! [`CommandRunner::status_report`], which renders as a table through
+//! `Display` or as JSON through [`StatusReport::to_json`].
+//!
+//! [`summarize`] turns the parsed packets into one [`CallSummary`]
+//! per call: who called whom, when it rang and was answered, who hung
+//! up and why.
+
+#[macro_use]
+extern crate lazy_static;
+
+pub mod bundle;
+pub mod capture;
+pub mod commands;
+pub mod e164;
+pub mod error;
+pub mod executor;
+pub mod filter;
+pub mod hep;
+pub mod isup;
+pub mod log_entry;
+pub mod modules;
+pub mod output;
+pub mod pcap;
+pub mod preflight;
+pub mod progress;
+pub mod raw;
+pub mod redact;
+pub mod render;
+pub mod select;
+pub mod sip_parse;
+mod ssh;
+pub mod status;
+pub mod summary;
+pub mod transaction;
+pub mod uri;
+pub mod window;
+
+pub use bundle::Bundle;
+pub use capture::CaptureSession;
+pub use commands::{
+    CommandRunner, ProcessInfo, RemoteClock, CIRPACK_PROCESSES,
+};
+pub use error::{Error, TraceResult};
+pub use executor::{LocalExecutor, RemoteExecutor, ScriptedExecutor};
+pub use filter::Filter;
+pub use hep::HepSender;
+pub use log_entry::{Endpoints, LogBlock, LogEntry, TimeOfDay};
+pub use modules::{ModuleType, ModuleTypes};
+pub use output::{Output, WriteMode};
+pub use preflight::{HostHealth, Limits};
+pub use progress::Progress;
+pub use raw::{CaptureInfo, RawCapture};
+pub use redact::Redactor;
+pub use render::{ColorChoice, HeaderFilter, Renderer};
+pub use select::{pick_process, ModuleSelector};
+pub use sip_parse::{Body, BodyPart, Framing, SipPacket, SipParser};
+pub use status::StatusReport;
+pub use summary::{summarize, CallSummary, Party};
+pub use transaction::{
+    collapse_retransmissions, find_issues, IssueKind, Message, TransactionIssue,
+};
+pub use uri::SipUri;
+pub use window::{TimeBound, TimeWindow};
@@ -0,0 +1,231 @@
+use regex::Regex;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// Time of day, with millisecond precision, of a log entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeOfDay(u32);
+
+impl TimeOfDay {
+    pub fn from_millis(millis: u32) -> Self {
+        Self(millis)
+    }
+
+    /// Milliseconds since midnight.
+    pub fn millis(self) -> u32 {
+        self.0
+    }
+
+    /// Parse `HH:MM`, `HH:MM:SS` or `HH:MM:SS.mmm`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (hms, frac) = match s.split_once('.') {
+            Some((hms, frac)) => (hms, Some(frac)),
+            None => (s, None),
+        };
+
+        let mut fields = hms.split(':').map(|f| f.parse::<u32>().ok());
+        let h = fields.next()??;
+        let m = fields.next()??;
+        let sec = fields.next().unwrap_or(Some(0))?;
+        if fields.next().is_some() || h > 23 || m > 59 || sec > 60 {
+            return None;
+        }
+
+        // Only the first three digits are milliseconds
+        let ms = match frac {
+            Some(f)
+                if !f.is_empty() && f.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                format!("{:0<3}", &f[..f.len().min(3)])
+                    .parse::<u32>()
+                    .ok()?
+            }
+            Some(_) => return None,
+            None => 0,
+        };
+
+        Some(Self(((h * 60 + m) * 60 + sec) * 1000 + ms))
+    }
+
+    /// Time elapsed since `earlier`, wrapping around midnight.
+    pub fn since(self, earlier: TimeOfDay) -> Duration {
+        const DAY: u32 = 24 * 60 * 60 * 1000;
+        Duration::from_millis(((self.0 + DAY - earlier.0) % DAY) as u64)
+    }
+
+    /// Unix time of this time of day, in UTC, on the day starting at
+    /// `day_start`. Without one, the last time the clock read it.
+    pub fn unix_time(self, day_start: Option<u64>) -> Duration {
+        let at = |midnight| {
+            Duration::from_secs(midnight) + Duration::from_millis(self.0 as u64)
+        };
+        if let Some(day_start) = day_start {
+            return at(day_start);
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let today = at(now.as_secs() - now.as_secs() % DAY_SECS);
+        if today > now {
+            today - Duration::from_secs(DAY_SECS)
+        } else {
+            today
+        }
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.0 % 1000;
+        let s = self.0 / 1000;
+        write!(
+            f,
+            "{:02}:{:02}:{:02}.{:03}",
+            s / 3600,
+            s / 60 % 60,
+            s % 60,
+            ms
+        )
+    }
+}
+
+/// The log line a SIP message was logged under, i.e.
+/// `10:15:20.123 [ibcf_core] SIP RECV from 10.1.1.1:5060 to 10.2.2.2:5060`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub line: String,
+    pub time: Option<TimeOfDay>,
+    pub source: Option<SocketAddr>,
+    pub destination: Option<SocketAddr>,
+}
+
+impl LogEntry {
+    pub fn parse(line: &str) -> Self {
+        lazy_static! {
+            static ref TIME: Regex =
+                Regex::new(r"\b(?P<time>\d{2}:\d{2}:\d{2}(?:\.\d+)?)\b")
+                    .unwrap();
+            static ref FROM: Regex =
+                Regex::new(r"\bfrom (?P<addr>\S+)").unwrap();
+            static ref TO: Regex = Regex::new(r"\bto (?P<addr>\S+)").unwrap();
+        }
+
+        let addr = |re: &Regex| {
+            re.captures(line)
+                .and_then(|c| c["addr"].trim_end_matches(',').parse().ok())
+        };
+
+        Self {
+            line: line.to_owned(),
+            time: TIME
+                .captures(line)
+                .and_then(|c| TimeOfDay::parse(&c["time"])),
+            source: addr(&FROM),
+            destination: addr(&TO),
+        }
+    }
+}
+
+/// Source and destination of a logged message, both in the same
+/// address family as the HEP and pcap exports need.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoints {
+    V4(SocketAddrV4, SocketAddrV4),
+    V6(SocketAddrV6, SocketAddrV6),
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        let unknown = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+        Endpoints::V4(unknown, unknown)
+    }
+}
+
+impl LogEntry {
+    /// Addresses missing from the line are `0.0.0.0:0`.
+    pub fn endpoints(&self) -> Endpoints {
+        let unknown = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0));
+        let src = self.source.unwrap_or(unknown);
+        let dst = self.destination.unwrap_or(unknown);
+        match (src, dst) {
+            (SocketAddr::V6(s), SocketAddr::V6(d)) => Endpoints::V6(s, d),
+            // Mixed families can't be expressed, IPv4 mapped it is
+            (s, d) => Endpoints::V4(
+                SocketAddrV4::new(to_ipv4(s.ip()), s.port()),
+                SocketAddrV4::new(to_ipv4(d.ip()), d.port()),
+            ),
+        }
+    }
+}
+
+fn to_ipv4(ip: IpAddr) -> Ipv4Addr {
+    match ip {
+        IpAddr::V4(ip) => ip,
+        IpAddr::V6(ip) => ip.to_ipv4_mapped().unwrap_or(Ipv4Addr::UNSPECIFIED),
+    }
+}
+
+/// Whether `line` starts a new log entry, i.e.
+/// `10:15:20.123 [ibcf_core] SIP RECV from ...`
+pub fn is_log_entry(line: &str) -> bool {
+    lazy_static! {
+        static ref LOG_ENTRY: Regex =
+            Regex::new(r"^(?:\d{4}[/-]\d{2}[/-]\d{2}[ T])?\d{2}:\d{2}:\d{2}\b")
+                .unwrap();
+    }
+
+    LOG_ENTRY.is_match(line)
+}
+
+/// A log entry line and the lines up to the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogBlock<'a> {
+    pub time: Option<TimeOfDay>,
+    pub lines: Vec<&'a str>,
+}
+
+impl LogBlock<'_> {
+    pub fn contains(&self, term: &str) -> bool {
+        self.lines.iter().any(|l| l.contains(term))
+    }
+}
+
+impl fmt::Display for LogBlock<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lines.iter().try_for_each(|l| writeln!(f, "{}", l))
+    }
+}
+
+/// Split `trace` into log entries. Lines before the first entry make
+/// up an untimed block.
+pub fn log_blocks(trace: &str) -> Vec<LogBlock<'_>> {
+    let mut blocks: Vec<LogBlock> = Vec::new();
+    for line in trace.lines() {
+        match blocks.last_mut() {
+            Some(block) if !is_log_entry(line) => block.lines.push(line),
+            _ => blocks.push(LogBlock {
+                time: if is_log_entry(line) {
+                    LogEntry::parse(line).time
+                } else {
+                    None
+                },
+                lines: vec![line],
+            }),
+        }
+    }
+    blocks
+}
+
+/// Time of the first log entry of `trace`.
+pub fn capture_start(trace: &str) -> Option<TimeOfDay> {
+    trace
+        .lines()
+        .filter(|l| is_log_entry(l))
+        .find_map(|l| LogEntry::parse(l).time)
+}
@@ -1,211 +1,696 @@
-#[macro_use]
-extern crate lazy_static;
-
-use error::{Error, TraceResult};
+use cirtrace::{
+    hep, log_entry, pcap, Bundle, CaptureInfo, CaptureSession, ColorChoice,
+    CommandRunner, Error, Filter, HeaderFilter, HepSender, Limits,
+    ModuleSelector, ModuleTypes, Output, Progress, RawCapture, Redactor,
+    Renderer, SipPacket, SipParser, TimeBound, TimeWindow, TraceResult,
+    WriteMode, CIRPACK_PROCESSES,
+};
+use clap::ArgMatches;
 use std::{
-    io::{self, Write},
+    io::{self, BufRead, IsTerminal, Write},
     path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
     thread,
-    time::{Duration, Instant},
+    time::{Duration, SystemTime},
 };
 
 mod args;
-mod commands;
-mod error;
-mod sip_parse;
-mod ssh;
-
-fn main() -> TraceResult<()> {
-    let matches = args::get_args();
-
-    let host = matches.value_of("host").unwrap();
-    let username = matches.value_of("username").unwrap_or("omni");
-    let password = matches.value_of("password").unwrap();
-    let timeout: u32 = matches
-        .value_of("trace-time")
-        .unwrap_or("15")
-        .parse()
-        .unwrap();
-    let process = matches.value_of("module");
-    let process_name = matches.value_of("module-name");
-    let instance = matches.value_of("instance");
 
-    let cmd = commands::CommandRunner::new(host, username, password)?;
+/// How long after `--trace-time` the host disables debug by itself,
+/// should cirtrace die or lose the connection first.
+const WATCHDOG_MARGIN: Duration = Duration::from_secs(60);
 
-    let ps_out = match (process, process_name, instance) {
-        (None, Some(pn), None) => cmd.get_ps_list(&[pn])?,
-        (Some(p), None, Some(i)) => {
-            cmd.get_ps_list(&[p, &format!("i{}", i)])?
-        }
-        (Some(p), None, None) => cmd.get_ps_list(&[p])?,
-        _ => String::new(),
-    };
+/// Debug may be on, Ctrl-C has to go through the capture.
+static DEBUGGING: AtomicBool = AtomicBool::new(false);
+/// Set by Ctrl-C while debug is on.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-    let pandi = find_process_and_instance(&ps_out);
+/// How long `--until-call-ends` keeps tracing after the call ends, for
+/// the stray ACKs and retransmissions.
+const CALL_END_GRACE: Duration = Duration::from_secs(3);
 
-    // Connect to node and set up the debugging
-    match pandi {
-        (Some(ref p), _, Some(ref i)) => {
-            cmd.enable_debug(p, i).and_then(|_| {
-                println!("Enabled debug mode");
-                Ok(())
-            })?;
-        }
-        _ => {}
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
     }
+}
 
-    // Get th time on remote system to the nearest minute
-    let remote_time = cmd.get_remote_time()?;
+fn run() -> TraceResult<()> {
+    let matches = args::get_args();
 
-    let pn = match pandi {
-        (Some(ref p), None, _) => Some(p),
-        (None, Some(ref pn), _) => Some(pn),
-        (Some(_), Some(ref pn), _) => Some(pn),
-        _ => None,
+    // Before a capture that would be lost to them
+    check_outputs(&matches)?;
+
+    let offline = match matches.value_of("input-file") {
+        Some(path) => Some(RawCapture::load(path)?),
+        None => None,
+    };
+    let cmd = match offline {
+        Some(_) => None,
+        None => {
+            let host = matches.value_of("host").unwrap();
+            let username = matches.value_of("username").unwrap_or("omni");
+            let password = matches.value_of("password").unwrap();
+            let modules = match matches.value_of("module-types") {
+                Some(path) => ModuleTypes::load(path)?,
+                None => ModuleTypes::default(),
+            };
+            let runner = CommandRunner::new(host, username, password)?;
+            Some(runner.module_types(modules))
+        }
     };
 
-    let trace_output = match pn {
-        // Tail the trace file only from the moment we started the test
-        Some(proc_name) => {
-            wait(Duration::from_secs(timeout as u64));
-            let trace_out = cmd.get_trace(&proc_name, &remote_time)?;
-            cmd.disable_debug(&pandi.0.unwrap(), &pandi.2.unwrap())
-                .and_then(|_| {
-                    println!("Disabled debugging");
-                    Ok(())
-                })?;
-            trace_out
+    let raw = match (offline, cmd.as_ref()) {
+        (Some(raw), _) => {
+            if raw.info != CaptureInfo::default() {
+                eprintln!("Read the capture of {}", raw.info);
+            }
+            raw
         }
-        None => "".to_string(),
+        (None, Some(cmd)) => capture(&matches, cmd)?,
+        (None, None) => RawCapture::default(),
+    };
+    let mut redactor = if matches.is_present("redact") {
+        let redactor = Redactor::new().ips(matches.is_present("redact-ips"));
+        let cc = matches
+            .subcommand_matches("sip")
+            .and_then(|s| s.value_of("normalize-numbers"));
+        Some(match cc {
+            Some(cc) => redactor.country_code(cc),
+            None => redactor,
+        })
+    } else {
+        None
     };
 
     match matches.subcommand() {
         ("sip", Some(s_match)) => {
-            let search_terms: Vec<_> =
-                if s_match.occurrences_of("search-term") > 0 {
-                    s_match.values_of("search-term").unwrap().collect()
-                } else {
-                    vec![]
-                };
-            // We have a full trace now so we can now
-            // extract interesting stuf from it
-            let sip_parser = sip_parse::SipParser::new();
-            let sip_packets =
-                sip_parser.extract_sip(&trace_output, &search_terms, true);
-
-            if matches.is_present("output-file") {
-                let out_file = matches.value_of("output-file").unwrap();
-                save_output_locally(&sip_packets, out_file)?;
+            sip(&matches, s_match, &raw, cmd.as_ref(), redactor.as_mut())?
+        }
+        ("trace", Some(t_match)) => {
+            trace(&matches, t_match, &raw.log, redactor.as_mut())?
+        }
+        ("status", Some(s_match)) => {
+            let cmd = match cmd {
+                Some(cmd) => cmd,
+                None => {
+                    eprintln!("The status subcommand needs a live host");
+                    return Ok(());
+                }
+            };
+            let processes: Vec<_> = match s_match.values_of("processes") {
+                Some(p) => p.collect(),
+                None => CIRPACK_PROCESSES.to_vec(),
+            };
+            eprintln!("Gathering info....");
+            let report = cmd.status_report(&processes)?;
+            match s_match.value_of("format") {
+                Some("json") => println!("{}", report.to_json()?),
+                _ => print!("{}", report),
             }
+        }
+        // clap requires one of the above
+        _ => unreachable!(),
+    };
+
+    if let (Some(r), Some(path)) = (redactor, matches.value_of("redact-map")) {
+        let lines: Vec<_> = r
+            .mapping()
+            .iter()
+            .map(|(original, pseudonym)| format!("{}\t{}", original, pseudonym))
+            .collect();
+        Output::new(path).mode(write_mode(&matches)).save(&lines)?;
+        eprintln!("Saved {} pseudonyms to {}", lines.len(), path);
+    }
+
+    Ok(())
+}
+
+/// The `sip` subcommand: the messages of the matched calls.
+fn sip(
+    matches: &ArgMatches,
+    s_match: &ArgMatches,
+    raw: &RawCapture,
+    cmd: Option<&CommandRunner>,
+    mut redactor: Option<&mut Redactor>,
+) -> TraceResult<()> {
+    let trace_output = &raw.log;
+    let search_terms = search_terms(s_match);
+    // We have a full trace now so we can now
+    // extract interesting stuf from it
+    eprintln!("Searching for {:?} terms in SIP packets", search_terms);
+    let filter = sip_filter(s_match)?;
+    let mut sip_packets = SipParser::new().extract(trace_output, &filter);
+    let window = time_window(s_match)?;
+    if let Some(start) = log_entry::capture_start(trace_output) {
+        sip_packets = window.packets(sip_packets, start);
+    }
+    if let Some(r) = redactor.as_mut() {
+        sip_packets = sip_packets.iter().map(|p| r.packet(p)).collect();
+    }
+
+    // Last, so that a failure doesn't cost the messages
+    let shown = show_packets(matches, s_match, &filter, &sip_packets);
+    if let Some(url) = s_match.value_of("hep") {
+        match send_hep(url, raw, &sip_packets) {
+            Ok(sent) => eprintln!("Sent {} messages to {}", sent, url),
+            Err(e) => eprintln!("Failed to send to {}: {}", url, e),
+        }
+    }
+    if let Some(path) = s_match.value_of("bundle") {
+        write_bundle(path, matches, raw, &sip_packets, cmd, redactor)?;
+        eprintln!("Saved the bundle to {}", path);
+    }
+    shown
+}
 
-            for p in sip_packets.iter() {
-                println!("{}", p);
+/// Send `packets` to the HEP server at `url`, returning how many were
+/// sent.
+fn send_hep(
+    url: &str,
+    raw: &RawCapture,
+    packets: &[SipPacket],
+) -> TraceResult<usize> {
+    let mut hep = HepSender::connect(url)?;
+    if let Some(day_start) = raw.info.day_start() {
+        hep = hep.day_start(day_start);
+    }
+    hep.send_all(packets)
+}
+
+/// Output of the `sip` subcommand: the matched messages or a summary
+/// of each call.
+fn show_packets(
+    matches: &ArgMatches,
+    s_match: &ArgMatches,
+    filter: &Filter,
+    sip_packets: &[SipPacket],
+) -> TraceResult<()> {
+    if s_match.value_of("format") == Some("summary") {
+        let summaries = cirtrace::summarize(sip_packets);
+        if let Some(out) = output(matches, "\n") {
+            out.save(&summaries)?;
+            if out.is_stdout() {
+                return Ok(());
             }
         }
-        ("status", _) => {
-            let _ = cmd.show_status();
+
+        for s in summaries.iter() {
+            println!("{}", s);
         }
-        _ => println!("{}", "Not supported yet"),
-    };
+        return Ok(());
+    }
+
+    let issues = cirtrace::find_issues(sip_packets);
+    let messages = cirtrace::collapse_retransmissions(sip_packets.to_vec());
+
+    let output = output(matches, "\n");
+    if let Some(ref out) = output {
+        let plain = renderer(s_match, false);
+        let rendered: Vec<_> =
+            messages.iter().map(|m| plain.message(m)).collect();
+        out.save(&rendered)?;
+    }
+
+    if !output.is_some_and(|o| o.is_stdout()) {
+        let color = matches
+            .value_of("color")
+            .and_then(ColorChoice::parse)
+            .unwrap_or(ColorChoice::Auto)
+            .enabled(io::stdout().is_terminal());
+        let renderer =
+            renderer(s_match, color).highlight(&filter.plain_terms());
+        for m in messages.iter() {
+            println!("{}", renderer.message(m));
+        }
+    }
+
+    if !issues.is_empty() {
+        eprintln!("Incomplete transactions:");
+        for i in issues.iter() {
+            eprintln!("  {}", i);
+        }
+    }
 
     Ok(())
 }
 
-fn save_output_locally<T: std::fmt::Display, P: AsRef<Path> + Copy>(
-    to_save: &[T],
-    filepath: P,
+/// The `trace` subcommand: the whole log, or the entries containing a
+/// search term.
+fn trace(
+    matches: &ArgMatches,
+    t_match: &ArgMatches,
+    trace_output: &str,
+    mut redactor: Option<&mut Redactor>,
 ) -> TraceResult<()> {
-    use std::fs::OpenOptions;
-    use std::io::BufWriter;
+    let search_terms: Vec<_> = match t_match.values_of("search-term") {
+        Some(t) => t.collect(),
+        None => vec![],
+    };
+    let mut blocks = log_entry::log_blocks(trace_output);
+    let window = time_window(t_match)?;
+    if let Some(start) = log_entry::capture_start(trace_output) {
+        blocks = window.blocks(blocks, start);
+    }
+    blocks.retain(|b| {
+        search_terms.is_empty() || search_terms.iter().any(|t| b.contains(t))
+    });
 
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(filepath)
-        .map_err(|e| Error::File(e, filepath.as_ref().to_path_buf()))?;
+    let blocks: Vec<_> = blocks
+        .iter()
+        .map(|b| match redactor.as_mut() {
+            Some(r) => r.log(&b.to_string()),
+            None => b.to_string(),
+        })
+        .collect();
 
-    let mut writer = BufWriter::new(file);
+    if let Some(out) = output(matches, "") {
+        out.save(&blocks)?;
+        if out.is_stdout() {
+            return Ok(());
+        }
+    }
 
-    for p in to_save.iter() {
-        let _ = writer
-            .write(p.to_string().as_bytes())
-            .map_err(Error::Write)?;
+    for b in blocks.iter() {
+        print!("{}", b);
     }
 
     Ok(())
 }
 
-/// This should find ("process", "process-name", "instance number")
-fn find_process_and_instance(
-    s: &str,
-) -> (Option<String>, Option<String>, Option<String>) {
-    // Extract the process from this:
-    // omni     28848  0.0  8.6 770804 714432 ?       Sl    2018   0:50 /home/omni/bin/ibcf -i1 -ribcf_core -f/home/etc/ibcf_core.cfg -tpip=254
+/// Trace the selected module for `--trace-time` seconds and fetch the
+/// log, saving it as is with `--save-raw`.
+fn capture(
+    matches: &ArgMatches,
+    cmd: &CommandRunner,
+) -> TraceResult<RawCapture> {
+    let max_trace_time = matches.value_of("max-trace-time").unwrap();
+    let interactive = matches.is_present("interactive");
+    let timeout: u64 = matches
+        .value_of("trace-time")
+        .unwrap_or(if interactive { max_trace_time } else { "15" })
+        .parse()
+        .unwrap();
+    let limits = Limits {
+        max_trace_time: Duration::from_secs(max_trace_time.parse().unwrap()),
+        ..Default::default()
+    };
+    let selector = ModuleSelector {
+        module: matches.value_of("module"),
+        module_name: matches.value_of("module-name"),
+        instance: matches.value_of("instance"),
+    };
 
-    let process = if let Some(idx1) = s.find(" /home") {
-        if let Some(idx2) = s[idx1..].find(" -i") {
-            if let Some(p) = s.get(idx1..idx1 + idx2) {
-                p.split("/").into_iter().last().map(|s| s.to_owned())
-            } else {
-                None
-            }
-        } else {
-            None
+    let process = match selector.ps_term() {
+        Some(term) => {
+            let candidates = cmd.find_processes(term)?;
+            let interactive = io::stdin().is_terminal();
+            cirtrace::pick_process(&selector, candidates, interactive)?
         }
+        None => return Ok(RawCapture::default()),
+    };
+
+    let trace_time = Duration::from_secs(timeout);
+    let force = matches.is_present("force");
+    check_host(cmd, &limits, trace_time, force)?;
+
+    let keys = if interactive {
+        let keys = keypresses();
+        eprintln!("Press Enter to enable debug on {}", process);
+        // Closed stdin starts right away
+        let _ = keys.recv();
+        // The host may have got busier while waiting
+        check_host(cmd, &limits, trace_time, force)?;
+        Some(keys)
     } else {
         None
     };
 
-    let process_name = if let Some(idx1) = s.find("-r") {
-        if let Some(idx2) = s[idx1..].find(" ") {
-            if let Some(p) = s.get(idx1..idx1 + idx2) {
-                // Just skip the -r
-                Some(p[2..].to_owned())
-            } else {
-                None
-            }
-        } else {
-            None
+    let mut info =
+        CaptureInfo::new(matches.value_of("host").unwrap(), &process);
+    info.start = Some(SystemTime::now());
+    stop_on_ctrl_c();
+    let mut session = CaptureSession::start(cmd, process)?;
+    let clock = session.start_clock();
+    info.remote_start = Some(clock.time.clone());
+    info.remote_unix_time = clock.unix_time;
+    info.utc_offset = clock.utc_offset;
+    if session.process().instance.is_some() {
+        eprintln!("Enabled debug mode");
+    }
+    let watchdog = trace_time + WATCHDOG_MARGIN;
+    if let Err(e) = session.watchdog(watchdog) {
+        eprintln!("Failed to leave a watchdog on the host: {}", e);
+    }
+
+    let s_match = matches.subcommand_matches("sip");
+    let filter = match s_match {
+        Some(s_match) => Some(sip_filter(s_match)?),
+        None => None,
+    };
+    let mut progress = Progress::new(trace_time);
+    if let Some(filter) = filter.as_ref() {
+        progress = progress.follow(filter);
+    }
+    let until_call_ends =
+        s_match.is_some_and(|s| s.is_present("until-call-ends"));
+    if until_call_ends {
+        progress = progress.until_call_ends(CALL_END_GRACE);
+    }
+    if interactive {
+        eprintln!("Press Enter or q to stop tracing");
+    }
+    let mut progress = watch(&session, progress, keys.as_ref());
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        // Dropping the session disables debug
+        drop(session);
+        DEBUGGING.store(false, Ordering::SeqCst);
+        return Err(Error::Interrupted);
+    }
+    if until_call_ends && progress.call_ended() {
+        eprintln!("The call ended, stopped the capture early");
+    }
+
+    let debugging = session.process().instance.is_some();
+    // What was logged since the last poll completes the followed log
+    let followed = match progress.poll(&session) {
+        Ok(()) => progress.followed_log(),
+        Err(_) => None,
+    };
+    let trace_out = match followed {
+        Some(log) => session.finish_followed(log),
+        None => session.finish(),
+    };
+    DEBUGGING.store(false, Ordering::SeqCst);
+    let trace_out = trace_out?;
+    info.end = Some(SystemTime::now());
+    if debugging {
+        eprintln!("Disabled debugging");
+    }
+
+    let raw = RawCapture::new(info, trace_out);
+    if let Some(path) = matches.value_of("save-raw") {
+        raw.save(path, matches.is_present("overwrite"))?;
+        eprintln!("Saved the raw log to {}", path);
+    }
+    Ok(raw)
+}
+
+/// Refuse to enable debug when the host is over `limits`, unless
+/// forced to.
+fn check_host(
+    cmd: &CommandRunner,
+    limits: &Limits,
+    trace_time: Duration,
+    force: bool,
+) -> TraceResult<()> {
+    // Unreadable figures are reported as unknown
+    let health = cmd.host_health().unwrap_or_default();
+    let problems = health.problems(limits, trace_time);
+    if !problems.is_empty() {
+        if !force {
+            return Err(Error::UnsafeToTrace(problems));
         }
-    } else {
-        None
+        for p in problems.iter() {
+            eprintln!("Tracing anyway: {}", p);
+        }
+    }
+    Ok(())
+}
+
+/// `--bundle`: everything about the capture in one archive. `packets`
+/// are already redacted with `--redact`. The status report and module
+/// config need the host, so they are left out offline.
+fn write_bundle(
+    path: &str,
+    matches: &ArgMatches,
+    raw: &RawCapture,
+    packets: &[SipPacket],
+    cmd: Option<&CommandRunner>,
+    mut redactor: Option<&mut Redactor>,
+) -> TraceResult<()> {
+    let redacted = redactor.is_some();
+    // Only the log, the header is rebuilt from the info so that its
+    // times stay as they are
+    let log = match redactor.as_mut() {
+        Some(r) => r.log(&raw.log),
+        None => raw.log.clone(),
+    };
+    let mut redact = |text: &str| match redactor.as_mut() {
+        Some(r) => r.text(text),
+        None => text.to_owned(),
     };
+    let mut info = raw.info.clone();
+    info.host = info.host.as_deref().map(&mut redact);
+    let raw_text = RawCapture::new(info.clone(), log).to_text();
+    let mut bundle = Bundle::new(info).redacted(redacted);
+    bundle.add("raw.log", "Log as fetched from the host", raw_text);
 
-    let instance = if let Some(idx1) = s.find("-i") {
-        if let Some(idx2) = s[idx1..].find(" ") {
-            if let Some(p) = s.get(idx1..idx1 + idx2) {
-                // Just skip the -i
-                Some(p[2..].to_owned())
-            } else {
-                None
+    bundle.add(
+        "sip.pcap",
+        "Matched SIP messages",
+        pcap::write(packets, raw.info.day_start()),
+    );
+    let summaries: Vec<_> = cirtrace::summarize(packets)
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    bundle.add(
+        "summary.txt",
+        "Summary of each matched call",
+        summaries.join("\n"),
+    );
+    let plain = Renderer::new(false);
+    let messages: Vec<_> = cirtrace::collapse_retransmissions(packets.to_vec())
+        .iter()
+        .map(|m| plain.message(m))
+        .collect();
+    bundle.add("sip.txt", "Matched SIP messages", messages.join("\n"));
+
+    if let Some(cmd) = cmd {
+        match cmd.status_report(CIRPACK_PROCESSES) {
+            Ok(report) => bundle.add(
+                "status.txt",
+                "Status of the host",
+                redact(&report.to_string()),
+            ),
+            Err(e) => eprintln!("Left the status report out: {}", e),
+        }
+        if let Some(ref config) = raw.info.config_file {
+            let name = Path::new(config)
+                .file_name()
+                .map_or("config".into(), |n| n.to_string_lossy());
+            match cmd.read_file(config) {
+                Ok(text) => bundle.add(
+                    &format!("config/{}", name),
+                    config,
+                    redact(&text),
+                ),
+                Err(e) => eprintln!("Left the module config out: {}", e),
             }
-        } else {
-            None
         }
+    }
+
+    bundle.write(path, matches.is_present("overwrite"))
+}
+
+fn search_terms<'a>(s_match: &'a ArgMatches) -> Vec<&'a str> {
+    if s_match.occurrences_of("search-term") > 0 {
+        s_match.values_of("search-term").unwrap().collect()
     } else {
-        None
+        vec![]
+    }
+}
+
+/// The calls to show: those with a message matching a search term or
+/// `--search-regex`.
+fn sip_filter(s_match: &ArgMatches) -> TraceResult<Filter> {
+    let search_terms = search_terms(s_match);
+    let mut filter = Filter::parse_terms(&search_terms)?;
+    if let Some(patterns) = s_match.values_of("search-regex") {
+        let mut filters = Vec::new();
+        if !search_terms.is_empty() {
+            filters.push(filter);
+        }
+        for p in patterns {
+            filters.push(Filter::regex(p)?);
+        }
+        filter = Filter::any(filters);
+    }
+    if let Some(cc) = s_match.value_of("normalize-numbers") {
+        filter = filter.with_e164(cc);
+    }
+    Ok(filter)
+}
+
+/// `--since`/`--until` of the `sip` and `trace` subcommands.
+fn time_window(s_match: &ArgMatches) -> TraceResult<TimeWindow> {
+    Ok(TimeWindow {
+        since: s_match
+            .value_of("since")
+            .map(TimeBound::parse)
+            .transpose()?,
+        until: s_match
+            .value_of("until")
+            .map(TimeBound::parse_until)
+            .transpose()?,
+    })
+}
+
+/// Renderer for the header filters of the `sip` subcommand.
+fn renderer<'a>(s_match: &ArgMatches<'a>, color: bool) -> Renderer<'a> {
+    let renderer = Renderer::new(color);
+    if s_match.is_present("compact") {
+        return renderer.compact();
+    }
+
+    let names = |arg| {
+        s_match
+            .values_of(arg)
+            .map(|v| v.map(|h: &str| h.to_owned()).collect::<Vec<_>>())
     };
+    match (names("headers"), names("hide-headers")) {
+        (Some(only), _) => renderer.headers(HeaderFilter::Only(only)),
+        (None, Some(hide)) => renderer.headers(HeaderFilter::Hide(hide)),
+        (None, None) => renderer,
+    }
+}
 
-    (process, process_name, instance)
+/// `--output-file`, separating items with `separator`.
+fn output(matches: &ArgMatches, separator: &str) -> Option<Output> {
+    matches.value_of("output-file").map(|path| {
+        Output::new(path)
+            .mode(write_mode(matches))
+            .separator(separator)
+    })
 }
 
-fn wait(wait_time: Duration) {
-    let stdout = io::stdout();
-    let mut handle = io::BufWriter::new(stdout);
-    let start = Instant::now();
+/// Refuse existing files up front: `--output-file`, `--save-raw`,
+/// `--redact-map` and `--bundle`, and a `--hep` URL that won't do.
+fn check_outputs(matches: &ArgMatches) -> TraceResult<()> {
+    if let Some(out) = output(matches, "") {
+        out.check()?;
+    }
+    if let Some(path) = matches.value_of("save-raw") {
+        Output::new(path).mode(overwrite_mode(matches)).check()?;
+    }
+    if let Some(path) = matches.value_of("redact-map") {
+        Output::new(path).mode(write_mode(matches)).check()?;
+    }
+    let sip = matches.subcommand_matches("sip");
+    if let Some(path) = sip.and_then(|s| s.value_of("bundle")) {
+        Output::new(path).mode(overwrite_mode(matches)).check()?;
+    }
+    if let Some(url) = sip.and_then(|s| s.value_of("hep")) {
+        hep::parse_url(url)?;
+    }
+    Ok(())
+}
+
+/// `--overwrite` for the files that are never appended to.
+fn overwrite_mode(matches: &ArgMatches) -> WriteMode {
+    if matches.is_present("overwrite") {
+        WriteMode::Overwrite
+    } else {
+        WriteMode::NoClobber
+    }
+}
 
-    println!("Awaiting test.\nElapsed: ");
-    loop {
-        if start.elapsed() > wait_time {
-            println!("\n");
+/// `--overwrite`/`--append`, refusing to clobber files by default.
+fn write_mode(matches: &ArgMatches) -> WriteMode {
+    if matches.is_present("overwrite") {
+        WriteMode::Overwrite
+    } else if matches.is_present("append") {
+        WriteMode::Append
+    } else {
+        WriteMode::NoClobber
+    }
+}
+
+/// Show how the capture is going until it is done, or a key is
+/// pressed with `--interactive`: a status line redrawn every second on
+/// a terminal, a plain line every few seconds otherwise.
+fn watch<'a>(
+    session: &CaptureSession,
+    mut progress: Progress<'a>,
+    keys: Option<&Receiver<()>>,
+) -> Progress<'a> {
+    const PLAIN_EVERY: u32 = 5;
+    let tty = io::stderr().is_terminal();
+    let mut width = 0;
+
+    // Follow the log from before the test call is placed
+    let _ = progress.poll(session);
+    eprintln!("Awaiting test.");
+    for tick in 0.. {
+        // A failed poll only leaves the figures as they were
+        let _ = progress.poll(session);
+        let done = progress.is_done();
+        if tty {
+            let line = progress.to_string();
+            // Pad over what is left of the previous line
+            eprint!("\r{:width$}", line, width = width);
+            let _ = io::stderr().flush();
+            width = line.len();
+        } else if done || (tick > 0 && tick % PLAIN_EVERY == 0) {
+            eprintln!("{}", progress);
+        }
+        if done || INTERRUPTED.load(Ordering::SeqCst) {
             break;
         }
-        thread::sleep(Duration::from_secs(1));
+        let pause = progress.remaining().min(Duration::from_secs(1));
+        match keys.map(|k| k.recv_timeout(pause)) {
+            Some(Ok(())) => break,
+            Some(Err(RecvTimeoutError::Timeout)) => {}
+            // No keys to wait for
+            Some(Err(RecvTimeoutError::Disconnected)) | None => {
+                thread::sleep(pause)
+            }
+        }
+    }
+    if tty {
+        eprintln!();
+    }
+    progress
+}
 
-        let _ = write!(handle, "{}{}", "\x1B[1000D", "\x1B[K");
-        let _ = handle.flush();
-        let _ = write!(handle, "{}s", start.elapsed().as_secs());
-        let _ = handle.flush();
+/// Have Ctrl-C end the capture rather than the process, so debug gets
+/// disabled on the way out.
+fn stop_on_ctrl_c() {
+    let handler = || {
+        if DEBUGGING.load(Ordering::SeqCst) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        } else {
+            std::process::exit(130);
+        }
+    };
+    DEBUGGING.store(true, Ordering::SeqCst);
+    if let Err(e) = ctrlc::set_handler(handler) {
+        eprintln!("Failed to handle Ctrl-C: {}", e);
     }
 }
+
+/// Enter, or q and Enter, on stdin. Lines are read in the background
+/// so the status line keeps going in between.
+fn keypresses() -> Receiver<()> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let key = line.trim();
+            if (key.is_empty() || key.eq_ignore_ascii_case("q"))
+                && tx.send(()).is_err()
+            {
+                break;
+            }
+        }
+    });
+    rx
+}
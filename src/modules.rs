@@ -0,0 +1,107 @@
+use crate::error::{Error, TraceResult};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// How to drive the debug mode of one Cirpack process type.
+///
+/// The command and path templates can use `{process}`, `{instance}`
+/// and `{name}` which are replaced with the binary name, the `-i`
+/// instance number and the `-r` instance name (or the binary name
+/// when it has none).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModuleType {
+    /// Binary name as seen in `ps`.
+    pub process: String,
+    pub enable_debug: String,
+    pub disable_debug: String,
+    /// Log file the trace is read from.
+    pub log_file: String,
+}
+
+/// Only the process types whose commands and log file have been
+/// checked on a live host. The others are described in a
+/// `--module-types` file.
+const BUILT_IN: &[&str] = &["ibcf", "bgcf"];
+
+impl ModuleType {
+    /// A process type driven through `mgt_cscf`, logging to
+    /// `/home/log/{name}.1`.
+    fn mgt_cscf(process: &str) -> Self {
+        Self {
+            process: process.to_owned(),
+            enable_debug: "mgt_cscf -name={process} -i{instance} -debug=3 \
+                           -loglevel=0"
+                .to_owned(),
+            disable_debug: "mgt_cscf -name={process} -i{instance} -debug=0 \
+                            -loglevel=1"
+                .to_owned(),
+            log_file: "/home/log/{name}.1".to_owned(),
+        }
+    }
+
+    pub fn render(
+        template: &str,
+        process: &str,
+        instance: Option<&str>,
+        name: Option<&str>,
+    ) -> String {
+        template
+            .replace("{process}", process)
+            .replace("{instance}", instance.unwrap_or(""))
+            .replace("{name}", name.unwrap_or(process))
+    }
+}
+
+/// The process types debug can be enabled on: the built-in ones plus
+/// any read from a JSON list of [`ModuleType`]s, i.e.
+///
+/// ```json
+/// [
+///   {
+///     "process": "gw_sip",
+///     "enable_debug": "<command enabling debug on -i{instance}>",
+///     "disable_debug": "<command disabling it again>",
+///     "log_file": "/home/log/{name}.1"
+///   }
+/// ]
+/// ```
+///
+/// An entry for a built-in type replaces it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleTypes(Vec<ModuleType>);
+
+impl Default for ModuleTypes {
+    fn default() -> Self {
+        Self(BUILT_IN.iter().map(|p| ModuleType::mgt_cscf(p)).collect())
+    }
+}
+
+impl ModuleTypes {
+    /// The built-in types and those listed in the file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> TraceResult<Self> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .map_err(|e| Error::File(e, path.to_owned()))?;
+        Self::parse(&json)
+            .map_err(|e| Error::InvalidModuleTypes(e, path.to_owned()))
+    }
+
+    /// The built-in types and those listed in `json`.
+    pub fn parse(json: &str) -> Result<Self, serde_json::Error> {
+        let mut types = Self::default();
+        for m in serde_json::from_str::<Vec<ModuleType>>(json)? {
+            types.0.retain(|t| t.process != m.process);
+            types.0.push(m);
+        }
+        Ok(types)
+    }
+
+    pub fn find(&self, process: &str) -> Option<&ModuleType> {
+        self.0.iter().find(|m| m.process == process)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.0.iter().map(|m| m.process.as_str()).collect()
+    }
+}
@@ -0,0 +1,155 @@
+//! Saving the output to a file.
+//!
+//! Files are written to a temporary file next to the target and renamed
+//! over it, so an interrupted run never leaves half a trace behind.
+
+use crate::error::{Error, TraceResult};
+use std::{
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+/// Path that writes to stdout instead of a file.
+pub const STDOUT: &str = "-";
+
+/// What to do when the output file already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Refuse to touch it.
+    #[default]
+    NoClobber,
+    Overwrite,
+    Append,
+}
+
+/// Where the output goes and how.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    path: PathBuf,
+    mode: WriteMode,
+    separator: String,
+}
+
+impl Output {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: WriteMode::default(),
+            separator: String::new(),
+        }
+    }
+
+    pub fn mode(mut self, mode: WriteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Written between items, after each item's own trailing newline.
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_owned();
+        self
+    }
+
+    pub fn is_stdout(&self) -> bool {
+        self.path == Path::new(STDOUT)
+    }
+
+    /// Fail the way saving would on an existing file, so that it can
+    /// be checked before the capture rather than after.
+    pub fn check(&self) -> TraceResult<()> {
+        let exists = !self.is_stdout() && self.path.exists();
+        if exists && self.mode == WriteMode::NoClobber {
+            return Err(Error::OutputExists(self.path.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn save<T: Display>(&self, items: &[T]) -> TraceResult<()> {
+        let mut content = String::new();
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                content.push_str(&self.separator);
+            }
+            content.push_str(&item.to_string());
+            if !content.ends_with('\n') {
+                content.push('\n');
+            }
+        }
+
+        self.save_bytes(content.as_bytes())
+    }
+
+    /// Write `content` as is. Appending adds the separator after the
+    /// existing content.
+    pub fn save_bytes(&self, content: &[u8]) -> TraceResult<()> {
+        if self.is_stdout() {
+            return io::stdout()
+                .lock()
+                .write_all(content)
+                .map_err(Error::Write);
+        }
+
+        let existing = match fs::read(&self.path) {
+            Ok(existing) => Some(existing),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(Error::File(e, self.path.clone())),
+        };
+        let mut bytes = Vec::new();
+        match (existing, self.mode) {
+            (Some(_), WriteMode::NoClobber) => {
+                return Err(Error::OutputExists(self.path.clone()))
+            }
+            (Some(existing), WriteMode::Append) if !existing.is_empty() => {
+                bytes = existing;
+                if !bytes.ends_with(b"\n") {
+                    bytes.push(b'\n');
+                }
+                bytes.extend_from_slice(self.separator.as_bytes());
+            }
+            _ => {}
+        }
+        bytes.extend_from_slice(content);
+
+        self.replace(&bytes)
+    }
+
+    /// Write `bytes` to a temporary file and rename it over the target.
+    fn replace(&self, bytes: &[u8]) -> TraceResult<()> {
+        let tmp = self.temp_path();
+        let write = || -> io::Result<()> {
+            let mut file =
+                OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+            file.write_all(bytes)?;
+            file.sync_all()
+        };
+        if let Err(e) = write() {
+            let _ = fs::remove_file(&tmp);
+            return Err(Error::File(e, tmp));
+        }
+
+        if let Ok(meta) = fs::metadata(&self.path) {
+            let _ = fs::set_permissions(&tmp, meta.permissions());
+        }
+        fs::rename(&tmp, &self.path).map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            Error::File(e, self.path.clone())
+        })
+    }
+
+    /// `.<name>.<pid>.tmp` in the same directory, so the rename doesn't
+    /// cross file systems.
+    fn temp_path(&self) -> PathBuf {
+        let name = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.path.with_file_name(format!(
+            ".{}.{}.tmp",
+            name,
+            std::process::id()
+        ))
+    }
+}
@@ -0,0 +1,131 @@
+//! pcap export of parsed SIP messages, to open the flow in Wireshark.
+//!
+//! Every message becomes a raw IP (no link layer) UDP packet between
+//! the addresses of its log entry. Addresses missing from the log are
+//! written as `0.0.0.0:0`.
+
+use crate::log_entry::{Endpoints, LogEntry};
+use crate::sip_parse::SipPacket;
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+const MAGIC: u32 = 0xa1b2_c3d4;
+const SNAPLEN: u32 = 65535;
+const LINKTYPE_RAW: u32 = 101;
+const IPPROTO_UDP: u8 = 17;
+const TTL: u8 = 64;
+const UDP_HEADER_LEN: usize = 8;
+/// Largest UDP payload an IP packet can carry.
+const MAX_PAYLOAD: usize = 65535 - 20 - UDP_HEADER_LEN;
+
+/// pcap file of `packets`, dated on the day starting at `day_start`
+/// (Unix time) when known.
+pub fn write(packets: &[SipPacket], day_start: Option<u64>) -> Vec<u8> {
+    let mut pcap = MAGIC.to_le_bytes().to_vec();
+    // Version 2.4
+    pcap.extend_from_slice(&2u16.to_le_bytes());
+    pcap.extend_from_slice(&4u16.to_le_bytes());
+    // Time zone and timestamp accuracy
+    pcap.extend_from_slice(&[0; 8]);
+    pcap.extend_from_slice(&SNAPLEN.to_le_bytes());
+    pcap.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+
+    for packet in packets {
+        let time = packet
+            .time()
+            .map(|t| t.unix_time(day_start))
+            .unwrap_or_default();
+        let data = ip_packet(packet);
+        record(&mut pcap, time, &data);
+    }
+    pcap
+}
+
+fn record(pcap: &mut Vec<u8>, time: Duration, data: &[u8]) {
+    pcap.extend_from_slice(&(time.as_secs() as u32).to_le_bytes());
+    pcap.extend_from_slice(&time.subsec_micros().to_le_bytes());
+    pcap.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    pcap.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    pcap.extend_from_slice(data);
+}
+
+/// IP and UDP headers around the wire form of `packet`.
+fn ip_packet(packet: &SipPacket) -> Vec<u8> {
+    let endpoints = packet
+        .log_entry()
+        .map(LogEntry::endpoints)
+        .unwrap_or_default();
+    let (src_port, dst_port) = match endpoints {
+        Endpoints::V4(s, d) => (s.port(), d.port()),
+        Endpoints::V6(s, d) => (s.port(), d.port()),
+    };
+
+    let mut payload = packet.to_bytes();
+    payload.truncate(MAX_PAYLOAD);
+    let udp_len = (UDP_HEADER_LEN + payload.len()) as u16;
+    let mut udp = Vec::with_capacity(udp_len as usize);
+    udp.extend_from_slice(&src_port.to_be_bytes());
+    udp.extend_from_slice(&dst_port.to_be_bytes());
+    udp.extend_from_slice(&udp_len.to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(&payload);
+
+    match endpoints {
+        Endpoints::V4(s, d) => ipv4(*s.ip(), *d.ip(), udp),
+        Endpoints::V6(s, d) => ipv6(*s.ip(), *d.ip(), udp),
+    }
+}
+
+fn ipv4(src: Ipv4Addr, dst: Ipv4Addr, udp: Vec<u8>) -> Vec<u8> {
+    let total_len = (20 + udp.len()) as u16;
+    let mut ip = vec![0x45, 0];
+    ip.extend_from_slice(&total_len.to_be_bytes());
+    // Identification, then don't fragment
+    ip.extend_from_slice(&[0, 0, 0x40, 0]);
+    ip.extend_from_slice(&[TTL, IPPROTO_UDP, 0, 0]);
+    ip.extend_from_slice(&src.octets());
+    ip.extend_from_slice(&dst.octets());
+    let checksum = checksum(&ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    // The UDP checksum is optional over IPv4
+    ip.extend(udp);
+    ip
+}
+
+fn ipv6(src: Ipv6Addr, dst: Ipv6Addr, mut udp: Vec<u8>) -> Vec<u8> {
+    let len = udp.len() as u32;
+    let mut pseudo = Vec::with_capacity(40 + udp.len());
+    pseudo.extend_from_slice(&src.octets());
+    pseudo.extend_from_slice(&dst.octets());
+    pseudo.extend_from_slice(&len.to_be_bytes());
+    pseudo.extend_from_slice(&[0, 0, 0, IPPROTO_UDP]);
+    pseudo.extend_from_slice(&udp);
+    let checksum = match checksum(&pseudo) {
+        0 => 0xffff,
+        c => c,
+    };
+    udp[6..8].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut ip = vec![0x60, 0, 0, 0];
+    ip.extend_from_slice(&(len as u16).to_be_bytes());
+    ip.extend_from_slice(&[IPPROTO_UDP, TTL]);
+    ip.extend_from_slice(&src.octets());
+    ip.extend_from_slice(&dst.octets());
+    ip.extend(udp);
+    ip
+}
+
+/// Internet checksum (RFC 1071).
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
@@ -0,0 +1,91 @@
+//! Checks before enabling debug on a production module.
+//!
+//! Debug logs every message in full, which can fill `/home/log` or
+//! load a busy IBCF further. [`CommandRunner::host_health`] takes a
+//! look at the host first and [`HostHealth::problems`] lists what is
+//! over the [`Limits`].
+//!
+//! [`CommandRunner::host_health`]: crate::CommandRunner::host_health
+
+use crate::status::{DiskUsage, LoadAverage, MAX_LOG_DISK_USAGE};
+use std::time::Duration;
+
+/// How far a capture may go.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Longest capture.
+    pub max_trace_time: Duration,
+    /// Free space to leave on `/home/log`, in kB.
+    pub min_free_kb: u64,
+    /// Highest `/home/log` usage, in percent.
+    pub max_disk_usage: u8,
+    /// Highest one minute load average per CPU.
+    pub max_load_per_cpu: f32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_trace_time: Duration::from_secs(600),
+            min_free_kb: 1024 * 1024,
+            max_disk_usage: MAX_LOG_DISK_USAGE,
+            max_load_per_cpu: 1.0,
+        }
+    }
+}
+
+/// Load and log disk usage of the host.
+#[derive(Debug, Default)]
+pub struct HostHealth {
+    pub load: Option<LoadAverage>,
+    pub cpus: Option<u32>,
+    pub log_disk: Option<DiskUsage>,
+}
+
+impl HostHealth {
+    /// Why tracing for `trace_time` would go over `limits`, if it
+    /// would. Figures that couldn't be read count as a problem too.
+    pub fn problems(
+        &self,
+        limits: &Limits,
+        trace_time: Duration,
+    ) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if trace_time > limits.max_trace_time {
+            problems.push(format!(
+                "trace time of {}s is over the maximum of {}s",
+                trace_time.as_secs(),
+                limits.max_trace_time.as_secs()
+            ));
+        }
+
+        match self.log_disk {
+            Some(ref d) if d.used_percent >= limits.max_disk_usage => problems
+                .push(format!("{}% used on {}", d.used_percent, d.mount)),
+            Some(ref d) if d.available_kb < limits.min_free_kb => problems
+                .push(format!(
+                    "only {} MB free on {}",
+                    d.available_kb / 1024,
+                    d.mount
+                )),
+            Some(_) => {}
+            None => problems.push("free space on /home/log unknown".into()),
+        }
+
+        match (self.load.as_ref(), self.cpus) {
+            (Some(load), Some(cpus)) => {
+                let max = limits.max_load_per_cpu * cpus as f32;
+                if load.one > max {
+                    problems.push(format!(
+                        "load of {:.2} is over {:.2} for {} CPUs",
+                        load.one, max, cpus
+                    ));
+                }
+            }
+            _ => problems.push("load unknown".into()),
+        }
+
+        problems
+    }
+}
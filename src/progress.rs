@@ -0,0 +1,301 @@
+//! Status line shown while a capture runs.
+//!
+//! ```text
+//! 12s elapsed, 3s left, log +48.2 kB, 2 INVITEs, latest 180 Ringing
+//! ```
+//!
+//! The log size is polled on the host. When following, the bytes
+//! added to the log are fetched too and the matching calls counted,
+//! and with [`Progress::until_call_ends`] the capture is cut short
+//! once one of them is over. Only what was added is parsed on each
+//! poll, and the followed log saves fetching it all again at the end.
+
+use crate::capture::CaptureSession;
+use crate::error::TraceResult;
+use crate::filter::Filter;
+use crate::log_entry::is_log_entry;
+use crate::sip_parse::{SipPacket, SipParser};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug)]
+pub struct Progress<'a> {
+    started: Instant,
+    duration: Duration,
+    filter: Option<&'a Filter>,
+    /// Log size at the first poll.
+    first_size: Option<u64>,
+    /// Log size at the latest poll.
+    size: Option<u64>,
+    /// Offset the log has been followed up to.
+    read: u64,
+    /// The first poll failed, so the followed log misses its start.
+    started_late: bool,
+    log: String,
+    /// Offset in `log` of the entry to parse from on the next poll.
+    parsed: usize,
+    /// Every call seen, by Call-ID.
+    calls: HashMap<String, Call>,
+    latest_status: Option<String>,
+    /// How long to keep going after a matching call ends.
+    grace: Option<Duration>,
+    /// When the end of a matching call showed up.
+    call_ended: Option<Instant>,
+}
+
+impl<'a> Progress<'a> {
+    /// Progress of a capture of `duration` starting now.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            started: Instant::now(),
+            duration,
+            filter: None,
+            first_size: None,
+            size: None,
+            read: 0,
+            started_late: false,
+            log: String::new(),
+            parsed: 0,
+            calls: HashMap::new(),
+            latest_status: None,
+            grace: None,
+            call_ended: None,
+        }
+    }
+
+    /// Follow the log, counting the INVITEs of the calls matching
+    /// `filter`.
+    pub fn follow(mut self, filter: &'a Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Be done `grace` after a followed call ends: a BYE is answered
+    /// with a 200 or the initial INVITE of a call that was never
+    /// answered gets a final failure response other than an
+    /// authentication challenge.
+    pub fn until_call_ends(mut self, grace: Duration) -> Self {
+        self.grace = Some(grace);
+        self
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn remaining(&self) -> Duration {
+        let remaining = self.duration.saturating_sub(self.elapsed());
+        match self.grace.zip(self.call_ended) {
+            Some((grace, ended)) => {
+                remaining.min(grace.saturating_sub(ended.elapsed()))
+            }
+            None => remaining,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining() == Duration::ZERO
+    }
+
+    /// Whether one of the followed calls is over.
+    pub fn call_ended(&self) -> bool {
+        self.call_ended.is_some()
+    }
+
+    /// Bytes added to the log since the first poll.
+    pub fn log_growth(&self) -> Option<u64> {
+        Some(self.size?.saturating_sub(self.first_size?))
+    }
+
+    /// Log followed so far.
+    pub fn log(&self) -> &str {
+        &self.log
+    }
+
+    /// The whole log written since the first poll, when following
+    /// from the start, so that it needn't be fetched again.
+    pub fn followed_log(self) -> Option<String> {
+        if self.filter.is_some() && !self.started_late {
+            Some(self.log)
+        } else {
+            None
+        }
+    }
+
+    /// INVITEs of the matching calls, retransmissions aside.
+    pub fn invites(&self) -> usize {
+        self.calls
+            .values()
+            .filter(|c| c.matched)
+            .map(|c| c.invites.len())
+            .sum()
+    }
+
+    /// Latest response to one of those INVITEs, i.e. `180 Ringing`.
+    pub fn latest_status(&self) -> Option<&str> {
+        self.latest_status.as_deref()
+    }
+
+    /// Check the size of the log and, when following, fetch what was
+    /// added to it since the last poll.
+    pub fn poll(&mut self, session: &CaptureSession) -> TraceResult<()> {
+        let size = session.log_size();
+        if size.is_err() && self.first_size.is_none() {
+            self.started_late = true;
+        }
+        let size = size?;
+        if self.first_size.is_none() {
+            self.first_size = Some(size);
+            self.read = size;
+        }
+        self.size = Some(size);
+
+        if let Some(filter) = self.filter {
+            if size > self.read {
+                let added = session.read_log(self.read, size - self.read)?;
+                self.read = size;
+                self.log.push_str(&added);
+                self.count(filter);
+            }
+        }
+        Ok(())
+    }
+
+    /// Go through the messages added to the log. The last entry may
+    /// have been cut short, so it is gone through again next time.
+    fn count(&mut self, filter: &Filter) {
+        let added = &self.log[self.parsed..];
+        if let Some(last) = last_entry_start(added) {
+            self.parsed += last;
+        }
+        for p in SipParser::new().parse(added) {
+            self.track(&p, filter);
+        }
+    }
+
+    fn track(&mut self, p: &SipPacket, filter: &Filter) {
+        let (call_id, seq, method) = match (p.call_id(), p.cseq()) {
+            (Some(call_id), Some((seq, method))) => (call_id, seq, method),
+            _ => return,
+        };
+        let call =
+            self.calls
+                .entry(call_id.to_owned())
+                .or_insert_with(|| Call {
+                    first_seq: seq,
+                    ..Default::default()
+                });
+        match (p.method(), method) {
+            (Some("INVITE"), _) => {
+                call.invites.insert(seq);
+                if p.to_tag().is_none() {
+                    call.initial.insert(seq);
+                }
+            }
+            (None, "INVITE") => {
+                call.latest_status =
+                    p.start_line().strip_prefix("SIP/2.0 ").map(str::to_owned);
+                let status = p.status_code().unwrap_or(0);
+                call.answered |= (200..300).contains(&status);
+                // Challenges are answered with a new INVITE, and a
+                // re-INVITE failing leaves the call up
+                let initial =
+                    call.initial.contains(&seq) || seq == call.first_seq;
+                call.ended |= (400..700).contains(&status)
+                    && status != 401
+                    && status != 407
+                    && initial
+                    && !call.answered;
+            }
+            (None, "BYE") => {
+                call.ended |= p.status_code() == Some(200);
+            }
+            _ => {}
+        }
+
+        // A call is followed from the first message matching
+        let newly_matched = !call.matched && filter.matches(p);
+        call.matched |= newly_matched;
+        if !call.matched {
+            return;
+        }
+        if newly_matched || p.method().is_none() && method == "INVITE" {
+            if let Some(ref status) = call.latest_status {
+                self.latest_status = Some(status.clone());
+            }
+        }
+        if call.ended && self.call_ended.is_none() {
+            self.call_ended = Some(Instant::now());
+        }
+    }
+}
+
+/// What is known of a call seen in the followed log.
+#[derive(Debug, Default)]
+struct Call {
+    /// One of its messages matches the filter.
+    matched: bool,
+    /// `CSeq` numbers of its INVITEs.
+    invites: HashSet<u32>,
+    /// First `CSeq` number logged.
+    first_seq: u32,
+    /// `CSeq` numbers of the INVITEs sent outside the dialog.
+    initial: HashSet<u32>,
+    /// An INVITE got a 2xx.
+    answered: bool,
+    latest_status: Option<String>,
+    ended: bool,
+}
+
+/// Offset of the last line of `log` starting a log entry.
+fn last_entry_start(log: &str) -> Option<usize> {
+    let mut offset = 0;
+    let mut last = None;
+    for line in log.split_inclusive('\n') {
+        if is_log_entry(line) {
+            last = Some(offset);
+        }
+        offset += line.len();
+    }
+    last
+}
+
+impl fmt::Display for Progress<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}s elapsed, {}s left",
+            self.elapsed().as_secs(),
+            // Rounded up, so it only reads 0s once done
+            (self.remaining().as_millis() as u64).div_ceil(1000)
+        )?;
+        if let Some(growth) = self.log_growth() {
+            write!(f, ", log +{}", bytes(growth))?;
+        }
+        if self.filter.is_some() {
+            match self.invites() {
+                1 => write!(f, ", 1 INVITE")?,
+                n => write!(f, ", {} INVITEs", n)?,
+            }
+            if let Some(status) = self.latest_status() {
+                write!(f, ", latest {}", status)?;
+            }
+            if self.call_ended() {
+                write!(f, ", call ended")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `512 B`, `48.2 kB`, `3.1 MB`.
+fn bytes(n: u64) -> String {
+    match n {
+        0..=999 => format!("{} B", n),
+        1_000..=999_999 => format!("{:.1} kB", n as f64 / 1e3),
+        _ => format!("{:.1} MB", n as f64 / 1e6),
+    }
+}
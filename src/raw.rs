@@ -0,0 +1,262 @@
+//! Raw captures: the exact log fetched from the SBC, saved with a
+//! short metadata header so it can be parsed again offline.
+//!
+//! ```text
+//! # cirtrace raw log
+//! # version: 0.1.10
+//! # host: 192.168.1.100
+//! # module: ibcf
+//! # module-name: ibcf_border
+//! # instance: 1
+//! # config-file: /home/omni/cfg/ibcf_border.cfg
+//! # start: 1760869800
+//! # end: 1760869815
+//! # remote-start: 10:30
+//! # remote-unix-time: 1760873400
+//! # utc-offset: 3600
+//!
+//! 10:30:00.000 [ibcf] SIP RECV from ...
+//! ```
+//!
+//! Files ending in `.gz` are gzip compressed. Files without the header
+//! are read as a bare log.
+
+use crate::commands::ProcessInfo;
+use crate::error::{Error, TraceResult};
+use crate::output::{Output, WriteMode};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use std::{
+    fmt,
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: &str = "# cirtrace raw log";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Where and when a log was captured.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaptureInfo {
+    /// cirtrace version that captured it.
+    pub version: String,
+    pub host: Option<String>,
+    /// Binary name, i.e. `ibcf`.
+    pub module: Option<String>,
+    /// Module instance name, i.e. `ibcf_border`.
+    pub module_name: Option<String>,
+    pub instance: Option<String>,
+    /// `-f` argument of the process.
+    pub config_file: Option<String>,
+    pub start: Option<SystemTime>,
+    pub end: Option<SystemTime>,
+    /// Remote time (`HH:MM`) the log was fetched from.
+    pub remote_start: Option<String>,
+    /// Unix time on the remote host when the capture started.
+    pub remote_unix_time: Option<u64>,
+    /// Offset of the remote clock from UTC, in seconds.
+    pub utc_offset: Option<i32>,
+}
+
+impl CaptureInfo {
+    /// Info about a capture of `process` on `host` by this version.
+    pub fn new(host: &str, process: &ProcessInfo) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            host: Some(host.to_owned()),
+            module: Some(process.process.clone()),
+            module_name: process.process_name.clone(),
+            instance: process.instance.clone(),
+            config_file: process.config_file.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Unix time of the remote midnight before the capture started,
+    /// to date the times of day in the log. Taken as UTC when the
+    /// remote clock wasn't recorded.
+    pub fn day_start(&self) -> Option<u64> {
+        const DAY: i64 = 24 * 60 * 60;
+        let (now, offset) = match (self.remote_unix_time, self.utc_offset) {
+            (Some(now), Some(offset)) => (now as i64, offset as i64),
+            _ => {
+                let start = self.start?.duration_since(UNIX_EPOCH).ok()?;
+                (start.as_secs() as i64, 0)
+            }
+        };
+        let local = now + offset;
+        Some((local - local.rem_euclid(DAY) - offset) as u64)
+    }
+
+    fn header(&self) -> String {
+        let secs = |t: Option<SystemTime>| {
+            t.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs().to_string())
+        };
+        let fields = [
+            ("version", Some(self.version.clone())),
+            ("host", self.host.clone()),
+            ("module", self.module.clone()),
+            ("module-name", self.module_name.clone()),
+            ("instance", self.instance.clone()),
+            ("config-file", self.config_file.clone()),
+            ("start", secs(self.start)),
+            ("end", secs(self.end)),
+            ("remote-start", self.remote_start.clone()),
+            (
+                "remote-unix-time",
+                self.remote_unix_time.map(|t| t.to_string()),
+            ),
+            ("utc-offset", self.utc_offset.map(|o| o.to_string())),
+        ];
+
+        let mut header = format!("{}\n", MAGIC);
+        for (key, value) in fields.iter() {
+            if let Some(value) = value {
+                header.push_str(&format!("# {}: {}\n", key, value));
+            }
+        }
+        header.push('\n');
+        header
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        let secs = || {
+            value
+                .parse()
+                .ok()
+                .map(|s| UNIX_EPOCH + Duration::from_secs(s))
+        };
+        match key {
+            "version" => self.version = value.to_owned(),
+            "host" => self.host = Some(value.to_owned()),
+            "module" => self.module = Some(value.to_owned()),
+            "module-name" => self.module_name = Some(value.to_owned()),
+            "instance" => self.instance = Some(value.to_owned()),
+            "config-file" => self.config_file = Some(value.to_owned()),
+            "start" => self.start = secs(),
+            "end" => self.end = secs(),
+            "remote-start" => self.remote_start = Some(value.to_owned()),
+            "remote-unix-time" => self.remote_unix_time = value.parse().ok(),
+            "utc-offset" => self.utc_offset = value.parse().ok(),
+            // Written by a newer version
+            _ => {}
+        }
+    }
+}
+
+/// `ibcf_border (ibcf -i1) on 192.168.1.100, captured by cirtrace 0.1.10`
+impl fmt::Display for CaptureInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = |v: &Option<String>| v.clone().unwrap_or("?".into());
+        write!(
+            f,
+            "{} ({} -i{}) on {}",
+            unknown(&self.module_name),
+            unknown(&self.module),
+            unknown(&self.instance),
+            unknown(&self.host)
+        )?;
+        if let Some(d) = self
+            .start
+            .zip(self.end)
+            .and_then(|(s, e)| e.duration_since(s).ok())
+        {
+            write!(f, " for {}s", d.as_secs())?;
+        }
+        if !self.version.is_empty() {
+            write!(f, ", captured by cirtrace {}", self.version)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawCapture {
+    pub info: CaptureInfo,
+    pub log: String,
+}
+
+impl RawCapture {
+    pub fn new(info: CaptureInfo, log: String) -> Self {
+        Self { info, log }
+    }
+
+    /// Split off the metadata header, if there is one.
+    pub fn parse(content: &str) -> Self {
+        let mut info = CaptureInfo::default();
+        let rest = match content.strip_prefix(MAGIC) {
+            Some(rest) => rest.strip_prefix('\n').unwrap_or(rest),
+            None => return Self::new(info, content.to_owned()),
+        };
+
+        let header_len = rest.find("\n\n").map_or(rest.len(), |i| i + 2);
+        for line in rest[..header_len].lines() {
+            let field = line.strip_prefix("# ").and_then(|l| {
+                let (key, value) = l.split_once(':')?;
+                Some((key.trim(), value.trim()))
+            });
+            if let Some((key, value)) = field {
+                info.set(key, value);
+            }
+        }
+        Self::new(info, rest[header_len..].to_owned())
+    }
+
+    pub fn to_text(&self) -> String {
+        self.info.header() + &self.log
+    }
+
+    /// Save to `path`, gzip compressed if it ends in `.gz`. Never
+    /// appends, an existing file is only replaced with `overwrite`.
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        overwrite: bool,
+    ) -> TraceResult<()> {
+        let path = path.as_ref();
+        let text = self.to_text();
+        let bytes = if is_gzip_path(path) {
+            let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+            gz.write_all(text.as_bytes())
+                .and_then(|_| gz.finish())
+                .map_err(Error::Write)?
+        } else {
+            text.into_bytes()
+        };
+
+        let mode = if overwrite {
+            WriteMode::Overwrite
+        } else {
+            WriteMode::NoClobber
+        };
+        Output::new(path).mode(mode).save_bytes(&bytes)
+    }
+
+    /// Read a capture saved by [`RawCapture::save`], or any plain or
+    /// gzipped log.
+    pub fn load<P: AsRef<Path>>(path: P) -> TraceResult<Self> {
+        let path = path.as_ref();
+        let file_error = |e| Error::File(e, path.to_path_buf());
+
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut bytes))
+            .map_err(file_error)?;
+        if bytes.starts_with(GZIP_MAGIC) {
+            let mut decoded = Vec::new();
+            MultiGzDecoder::new(&bytes[..])
+                .read_to_end(&mut decoded)
+                .map_err(file_error)?;
+            bytes = decoded;
+        }
+
+        Ok(Self::parse(&String::from_utf8_lossy(&bytes)))
+    }
+}
+
+fn is_gzip_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("gz"))
+}
@@ -0,0 +1,257 @@
+//! Pseudonymising customer data before traces leave the building.
+//!
+//! Phone numbers keep their prefix and get their last seven digits
+//! replaced by a sequence number, the same one everywhere the number
+//! shows up, so `+353871234567` becomes `+353870000001` and a call flow
+//! stays readable. Pseudonyms have as many digits as the original, so
+//! `Content-Length` stays right. Other SIP URI users become `u0001`
+//! and friends, and with [`Redactor::ips`] IPv4 addresses are mapped
+//! into `198.18.0.0/15`.
+//!
+//! Numbers outside of URIs are only taken as phone numbers in the
+//! headers naming a party, such as a display name in `From`, and in
+//! ISUP bodies. Elsewhere a run of digits is as likely an SDP session
+//! ID or part of a Call-ID, which have to stay as they are.
+
+use crate::e164;
+use crate::isup;
+use crate::sip_parse::{header_name, SipPacket};
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+/// Digits replaced at the end of each number.
+const SUBSCRIBER_DIGITS: usize = 7;
+
+/// Headers naming a party, whose bare numbers are phone numbers too.
+const NUMBER_HEADERS: &[&str] = &[
+    "From",
+    "To",
+    "Contact",
+    "P-Asserted-Identity",
+    "P-Preferred-Identity",
+    "P-Called-Party-ID",
+    "Remote-Party-ID",
+    "Diversion",
+    "History-Info",
+    "Referred-By",
+    "Refer-To",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Number,
+    User,
+    Ip,
+}
+
+#[derive(Debug, Default)]
+pub struct Redactor {
+    country_code: Option<String>,
+    ips: bool,
+    /// Sequence number of each value, by kind and normalised form.
+    ids: HashMap<(Kind, String), usize>,
+    /// Next sequence number of each kind.
+    next: HashMap<Kind, usize>,
+    /// Original and pseudonym, in the order first seen.
+    mapping: Vec<(String, String)>,
+}
+
+impl Redactor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Country code of national (`0...`) numbers, so they get the same
+    /// pseudonym as their international form.
+    pub fn country_code(mut self, country_code: &str) -> Self {
+        self.country_code = Some(country_code.to_owned());
+        self
+    }
+
+    /// Pseudonymise IPv4 addresses too.
+    pub fn ips(mut self, ips: bool) -> Self {
+        self.ips = ips;
+        self
+    }
+
+    /// `text` with every URI user, phone number and, if enabled, IP
+    /// address pseudonymised. `text` is a single line, or a number on
+    /// its own.
+    pub fn text(&mut self, text: &str) -> String {
+        lazy_static! {
+            // One pass, so pseudonyms are never pseudonymised again
+            static ref PII: Regex = Regex::new(
+                r#"(?x)
+                (?P<scheme>\bsips?:)(?P<user>[^@;:>\s"]+)@
+                | \btel:(?P<tel>\+?[\d\-.()]+)
+                | (?P<number>\+\d{7,15}\b|\b\d{7,15}\b)
+                | (?P<ip>\b\d{1,3}(?:\.\d{1,3}){3}\b)
+                "#
+            )
+            .unwrap();
+            static ref BARE_NUMBER: Regex = Regex::new(r"^\+?\d+$").unwrap();
+        }
+        let numbers = BARE_NUMBER.is_match(text)
+            || header_name(text).is_some_and(|n| {
+                NUMBER_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(n))
+            });
+        PII.replace_all(text, |c: &Captures| {
+            if let Some(user) = c.name("user") {
+                format!("{}{}@", &c["scheme"], self.user(user.as_str()))
+            } else if let Some(tel) = c.name("tel") {
+                format!("tel:{}", self.tel(tel.as_str()))
+            } else if let Some(number) = c.name("number") {
+                if numbers {
+                    self.number(number.as_str())
+                } else {
+                    number.as_str().to_owned()
+                }
+            } else if self.ips {
+                self.ip(&c[0])
+            } else {
+                c[0].to_owned()
+            }
+        })
+        .into_owned()
+    }
+
+    /// A whole log, line by line as [`Redactor::text`], but with the
+    /// hex of ISUP bodies decoded so that their numbers go too.
+    pub fn log(&mut self, log: &str) -> String {
+        let mut out = String::with_capacity(log.len());
+        // After an ISUP Content-Type, until the blank line
+        let mut isup_headers = false;
+        // Hex lines of the ISUP body so far
+        let mut hex: Option<Vec<&str>> = None;
+
+        for line in log.split_inclusive('\n') {
+            let text = line.trim_end();
+            if let Some(lines) = hex.as_mut() {
+                if isup::parse_hex(text).is_some() {
+                    lines.push(line);
+                    continue;
+                }
+                let lines = hex.take().unwrap_or_default();
+                out.push_str(&self.isup_hex(&lines));
+            }
+
+            if isup_headers && text.is_empty() {
+                isup_headers = false;
+                hex = Some(Vec::new());
+            } else if is_isup_content_type(text) {
+                isup_headers = true;
+            }
+            let ending = &line[text.len()..];
+            out.push_str(&self.text(text));
+            out.push_str(ending);
+        }
+        if let Some(lines) = hex {
+            out.push_str(&self.isup_hex(&lines));
+        }
+        out
+    }
+
+    /// Hex `lines` of an ISUP message with the numbers replaced, or as
+    /// they are when there are none.
+    fn isup_hex(&mut self, lines: &[&str]) -> String {
+        let bytes = isup::parse_hex(&lines.concat()).unwrap_or_default();
+        let mapped = isup::map_numbers(&bytes, |n| self.text(n));
+        if mapped == bytes {
+            return lines.concat();
+        }
+        let last = lines.last().copied().unwrap_or_default();
+        isup::to_hex(&mapped) + &last[last.trim_end().len()..]
+    }
+
+    /// Copy of `packet` with [`Redactor::text`] applied throughout,
+    /// including the numbers of ISUP parts.
+    pub fn packet(&mut self, packet: &SipPacket) -> SipPacket {
+        packet.map_text(|l| self.text(l))
+    }
+
+    /// Every value replaced so far and its pseudonym.
+    pub fn mapping(&self) -> &[(String, String)] {
+        &self.mapping
+    }
+
+    fn id(&mut self, kind: Kind, key: String) -> usize {
+        let next = &mut self.next;
+        *self.ids.entry((kind, key)).or_insert_with(|| {
+            let id = next.entry(kind).or_insert(0);
+            *id += 1;
+            *id
+        })
+    }
+
+    fn record(&mut self, original: &str, pseudonym: String) -> String {
+        if !self.mapping.iter().any(|(o, _)| o == original) {
+            self.mapping.push((original.to_owned(), pseudonym.clone()));
+        }
+        pseudonym
+    }
+
+    fn number(&mut self, number: &str) -> String {
+        let key = self
+            .country_code
+            .as_deref()
+            .and_then(|cc| e164::normalize(number, cc))
+            .unwrap_or_else(|| number.trim_start_matches('+').to_owned());
+        let id = self.id(Kind::Number, key);
+
+        let keep = number.len().saturating_sub(SUBSCRIBER_DIGITS);
+        let width = number.len() - keep;
+        let pseudonym = format!("{}{:0width$}", &number[..keep], id);
+        self.record(number, pseudonym)
+    }
+
+    /// Number with visual separators, which stay where they are.
+    fn tel(&mut self, tel: &str) -> String {
+        let digits: String = tel
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == '+')
+            .collect();
+        let mut pseudonym = self.number(&digits).into_bytes().into_iter();
+        let tel = tel
+            .chars()
+            .map(|c| match c {
+                '0'..='9' | '+' => pseudonym.next().map_or(c, |d| d as char),
+                c => c,
+            })
+            .collect();
+        // Grown past the original number of digits
+        pseudonym.fold(tel, |mut tel: String, d| {
+            tel.push(d as char);
+            tel
+        })
+    }
+
+    fn user(&mut self, user: &str) -> String {
+        let digits = user.strip_prefix('+').unwrap_or(user);
+        if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+            return self.number(user);
+        }
+
+        let id = self.id(Kind::User, user.to_owned());
+        let width = user.len().saturating_sub(1).max(1);
+        self.record(user, format!("u{:0width$}", id))
+    }
+
+    fn ip(&mut self, ip: &str) -> String {
+        let n = self.id(Kind::Ip, ip.to_owned());
+        let pseudonym = format!(
+            "198.{}.{}.{}",
+            18 + (n >> 16) % 2,
+            (n >> 8) & 0xff,
+            n & 0xff
+        );
+        self.record(ip, pseudonym)
+    }
+}
+
+fn is_isup_content_type(line: &str) -> bool {
+    let line = line.to_ascii_lowercase();
+    let value = line
+        .strip_prefix("content-type:")
+        .or_else(|| line.strip_prefix("c:"));
+    value.is_some_and(|v| v.trim_start().starts_with("application/isup"))
+}
@@ -0,0 +1,267 @@
+//! Terminal rendering of SIP messages.
+//!
+//! Without colour the output is exactly the `Display` output of the
+//! messages, so it can still be piped or saved.
+
+use crate::sip_parse::{header_name, Framing, SipPacket};
+use crate::transaction::Message;
+use std::env;
+
+const RESET: &str = "\x1B[0m";
+const BOLD: &str = "\x1B[1m";
+const DIM: &str = "\x1B[2m";
+const RED: &str = "\x1B[31m";
+const GREEN: &str = "\x1B[32m";
+const YELLOW: &str = "\x1B[33m";
+const REQUEST: &str = "\x1B[1;36m";
+/// Reverse video on and off, leaving the colour of the line alone.
+const MATCH_ON: &str = "\x1B[7m";
+const MATCH_OFF: &str = "\x1B[27m";
+
+/// Headers kept by [`Renderer::compact`].
+const COMPACT_VIEW: &[&str] = &["From", "To", "Call-ID", "CSeq", "Contact"];
+
+/// SDP direction attributes.
+const DIRECTIONS: &[&str] =
+    &["a=sendrecv", "a=sendonly", "a=recvonly", "a=inactive"];
+
+/// Headers that identify the call.
+const KEY_HEADERS: &[&str] = &["Call-ID", "From", "To", "Contact"];
+
+/// Headers that rarely matter when troubleshooting a call.
+const DIM_HEADERS: &[&str] = &[
+    "Via",
+    "Route",
+    "Record-Route",
+    "Max-Forwards",
+    "User-Agent",
+    "Server",
+    "Allow",
+    "Allow-Events",
+    "Supported",
+    "Accept",
+    "Content-Length",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn names() -> Vec<&'static str> {
+        vec!["auto", "always", "never"]
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    /// Whether to colour output going to a terminal, or not. `auto`
+    /// respects `NO_COLOR` (https://no-color.org).
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                is_terminal
+                    && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        }
+    }
+}
+
+/// Which headers to print. Names are case insensitive and match the
+/// compact forms too.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HeaderFilter {
+    #[default]
+    All,
+    Only(Vec<String>),
+    Hide(Vec<String>),
+}
+
+impl HeaderFilter {
+    fn shows(&self, line: &str) -> bool {
+        let listed = |names: &[String]| {
+            header_name(line).is_some_and(|n| {
+                names.iter().any(|h| h.eq_ignore_ascii_case(n))
+            })
+        };
+        match self {
+            HeaderFilter::All => true,
+            HeaderFilter::Only(names) => listed(names),
+            HeaderFilter::Hide(names) => !listed(names),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Renderer<'a> {
+    color: bool,
+    highlight: Vec<&'a str>,
+    headers: HeaderFilter,
+    /// Replace the body with a one line SDP summary.
+    sdp_summary: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(color: bool) -> Self {
+        Self {
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub fn headers(mut self, filter: HeaderFilter) -> Self {
+        self.headers = filter;
+        self
+    }
+
+    /// Only the start line, `From`, `To`, `Call-ID`, `CSeq`, `Contact`
+    /// and a summary of the SDP.
+    pub fn compact(mut self) -> Self {
+        self.headers = HeaderFilter::Only(
+            COMPACT_VIEW.iter().map(|h| h.to_string()).collect(),
+        );
+        self.sdp_summary = true;
+        self
+    }
+
+    /// Highlight every occurrence of `terms`, case insensitive.
+    pub fn highlight(mut self, terms: &[&'a str]) -> Self {
+        self.highlight =
+            terms.iter().filter(|t| !t.is_empty()).copied().collect();
+        self
+    }
+
+    pub fn packet(&self, packet: &SipPacket) -> String {
+        let mut out = String::new();
+
+        self.line(
+            &mut out,
+            packet.start_line(),
+            Some(start_line_style(packet)),
+        );
+        // Folded lines go with the header they continue
+        let (mut shown, mut style) = (false, None);
+        for h in packet.headers() {
+            if !h.starts_with([' ', '\t']) {
+                shown = self.headers.shows(h);
+                style = header_style(h);
+            }
+            if shown {
+                self.line(&mut out, h, style);
+            }
+        }
+
+        if self.sdp_summary {
+            if let Some(sdp) = packet.sdp() {
+                self.line(&mut out, &sdp_summary(sdp), Some(DIM));
+            }
+        } else if let Some(body) = packet.body() {
+            out.push('\n');
+            for l in body.to_string().lines() {
+                self.line(&mut out, l, None);
+            }
+            out.push('\n');
+        }
+
+        if packet.framing() != Framing::Complete {
+            self.line(&mut out, &packet.framing().to_string(), Some(RED));
+        }
+
+        out
+    }
+
+    pub fn message(&self, message: &Message) -> String {
+        let mut out = self.packet(&message.packet);
+        if let Some(note) = message.retransmission_note() {
+            self.line(&mut out, &note, Some(YELLOW));
+        }
+        out
+    }
+
+    fn line(&self, out: &mut String, text: &str, style: Option<&str>) {
+        match style {
+            Some(style) if self.color => {
+                out.push_str(style);
+                self.highlighted(out, text);
+                out.push_str(RESET);
+            }
+            _ => self.highlighted(out, text),
+        }
+        out.push('\n');
+    }
+
+    fn highlighted(&self, out: &mut String, text: &str) {
+        if !self.color || self.highlight.is_empty() {
+            out.push_str(text);
+            return;
+        }
+
+        // ASCII lowercasing keeps the byte offsets of `text`
+        let lower = text.to_ascii_lowercase();
+        let terms: Vec<_> = self
+            .highlight
+            .iter()
+            .map(|t| t.to_ascii_lowercase())
+            .collect();
+
+        let mut pos = 0;
+        while let Some((start, len)) = terms
+            .iter()
+            .filter_map(|t| {
+                lower[pos..].find(t.as_str()).map(|i| (pos + i, t.len()))
+            })
+            .min_by_key(|&(start, len)| (start, usize::MAX - len))
+        {
+            out.push_str(&text[pos..start]);
+            out.push_str(MATCH_ON);
+            out.push_str(&text[start..start + len]);
+            out.push_str(MATCH_OFF);
+            pos = start + len;
+        }
+        out.push_str(&text[pos..]);
+    }
+}
+
+fn start_line_style(packet: &SipPacket) -> &'static str {
+    match packet.status_code() {
+        None => REQUEST,
+        Some(100..=199) => YELLOW,
+        Some(200..=299) => GREEN,
+        Some(_) => RED,
+    }
+}
+
+fn header_style(line: &str) -> Option<&'static str> {
+    let name = header_name(line)?;
+    let is = |list: &[&str]| list.iter().any(|h| h.eq_ignore_ascii_case(name));
+    if is(KEY_HEADERS) {
+        Some(BOLD)
+    } else if is(DIM_HEADERS) {
+        Some(DIM)
+    } else {
+        None
+    }
+}
+
+/// `SDP: c=IN IP4 10.1.1.1; m=audio 20000 RTP/AVP 8 101; a=sendrecv`
+fn sdp_summary(sdp: &[String]) -> String {
+    let fields: Vec<_> = sdp
+        .iter()
+        .map(|l| l.trim())
+        .filter(|l| {
+            l.starts_with("c=") || l.starts_with("m=") || DIRECTIONS.contains(l)
+        })
+        .collect();
+    format!("SDP: {}", fields.join("; "))
+}
@@ -0,0 +1,101 @@
+use crate::commands::ProcessInfo;
+use crate::error::{Error, TraceResult};
+use std::io::{self, BufRead, Write};
+
+/// What the user asked for on the command line.
+#[derive(Debug, Default)]
+pub struct ModuleSelector<'a> {
+    /// `-m`: process type, i.e. ibcf
+    pub module: Option<&'a str>,
+    /// `-M`: process instance name, i.e. ibcf_core
+    pub module_name: Option<&'a str>,
+    /// `-i`: instance number
+    pub instance: Option<&'a str>,
+}
+
+impl<'a> ModuleSelector<'a> {
+    /// Term used to narrow down the remote `ps` listing.
+    pub fn ps_term(&self) -> Option<&'a str> {
+        self.module_name.or(self.module)
+    }
+
+    fn matches(&self, p: &ProcessInfo) -> bool {
+        self.module.is_none_or(|m| p.process == m)
+            && self
+                .module_name
+                .is_none_or(|n| p.process_name.as_deref() == Some(n))
+            && self
+                .instance
+                .is_none_or(|i| p.instance.as_deref() == Some(i))
+    }
+
+    fn describe(&self) -> String {
+        let mut d = Vec::new();
+        if let Some(m) = self.module {
+            d.push(format!("module {}", m));
+        }
+        if let Some(n) = self.module_name {
+            d.push(format!("module name {}", n));
+        }
+        if let Some(i) = self.instance {
+            d.push(format!("instance {}", i));
+        }
+        d.join(", ")
+    }
+}
+
+/// Narrow `candidates` down to a single process.
+///
+/// When more than one process matches the selector the user is
+/// asked on stdin to choose one if `interactive` is set, otherwise
+/// we bail out with the list of candidates.
+pub fn pick_process(
+    selector: &ModuleSelector,
+    candidates: Vec<ProcessInfo>,
+    interactive: bool,
+) -> TraceResult<ProcessInfo> {
+    let mut matching: Vec<_> = candidates
+        .into_iter()
+        .filter(|p| selector.matches(p))
+        .collect();
+
+    match matching.len() {
+        0 => Err(Error::ModuleNotFound(selector.describe())),
+        1 => Ok(matching.remove(0)),
+        _ if interactive => {
+            let idx = prompt(&matching)?;
+            Ok(matching.remove(idx))
+        }
+        _ => Err(Error::AmbiguousModule(
+            matching.iter().map(|p| p.to_string()).collect(),
+        )),
+    }
+}
+
+fn prompt(candidates: &[ProcessInfo]) -> TraceResult<usize> {
+    let stdin = io::stdin();
+    let mut input = String::new();
+
+    eprintln!("Multiple processes match:");
+    for (n, p) in candidates.iter().enumerate() {
+        eprintln!("  [{}] {}", n + 1, p);
+    }
+
+    loop {
+        eprint!("Select process [1-{}, q to quit]: ", candidates.len());
+        let _ = io::stderr().flush();
+
+        input.clear();
+        if stdin.lock().read_line(&mut input).map_err(Error::Prompt)? == 0 {
+            return Err(Error::SelectionAborted);
+        }
+
+        match input.trim() {
+            "q" | "Q" => return Err(Error::SelectionAborted),
+            i => match i.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= candidates.len() => return Ok(n - 1),
+                _ => eprintln!("Invalid choice: {}", i),
+            },
+        }
+    }
+}
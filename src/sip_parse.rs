@@ -1,33 +1,510 @@
+use crate::filter::Filter;
+use crate::isup::{self, IsupSummary};
+use crate::log_entry::{is_log_entry, LogEntry, TimeOfDay};
+use crate::uri::SipUri;
+
+/// Compact forms of the headers we look at (RFC 3261 section 7.3.3).
+const COMPACT_HEADERS: &[(&str, &str)] = &[
+    ("Call-ID", "i"),
+    ("Content-Length", "l"),
+    ("Content-Type", "c"),
+    ("From", "f"),
+    ("To", "t"),
+    ("Via", "v"),
+    ("Contact", "m"),
+];
+
 const SDP_ATTRIBUTES: &[&str] = &[
     "v=", "o=", "s=", "i=", "u=", "e=", "p=", "c=", "b=", "z=", "k=", "a=",
     "t=", "r=", "m=",
 ];
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SipPacket {
     header: SipHeader,
-    sdp: Option<Sdp>,
+    body: Option<Body>,
+    framing: Framing,
+    log_entry: Option<LogEntry>,
+}
+
+impl SipPacket {
+    /// Request or status line, i.e. `INVITE sip:123@10.1.1.1 SIP/2.0`.
+    pub fn start_line(&self) -> &str {
+        self.header.0.first().map(|s| s.as_str()).unwrap_or("")
+    }
+
+    /// Every header line, without the start line.
+    pub fn headers(&self) -> &[String] {
+        self.header.0.get(1..).unwrap_or(&[])
+    }
+
+    /// Value of the first header called `name`, case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.header.value(name)
+    }
+
+    pub fn call_id(&self) -> Option<&str> {
+        self.header.value("Call-ID")
+    }
+
+    pub fn body(&self) -> Option<&Body> {
+        self.body.as_ref()
+    }
+
+    /// Whether the whole message made it into the log.
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// The log line the message was logged under.
+    pub fn log_entry(&self) -> Option<&LogEntry> {
+        self.log_entry.as_ref()
+    }
+
+    pub fn time(&self) -> Option<TimeOfDay> {
+        self.log_entry.as_ref()?.time
+    }
+
+    /// Method of a request, `None` for responses.
+    pub fn method(&self) -> Option<&str> {
+        let start = self.start_line();
+        if start.starts_with("SIP/2.0") {
+            None
+        } else {
+            start.split_whitespace().next()
+        }
+    }
+
+    /// Status code of a response, `None` for requests.
+    pub fn status_code(&self) -> Option<u16> {
+        self.start_line()
+            .strip_prefix("SIP/2.0 ")?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    }
+
+    /// Sequence number and method of the `CSeq` header.
+    pub fn cseq(&self) -> Option<(u32, &str)> {
+        let mut cseq = self.header("CSeq")?.split_whitespace();
+        Some((cseq.next()?.parse().ok()?, cseq.next()?))
+    }
+
+    /// `branch` parameter of the topmost `Via`.
+    pub fn via_branch(&self) -> Option<&str> {
+        self.header("Via")?.split(';').skip(1).find_map(|p| {
+            let (n, v) = p.split_once('=')?;
+            if n.trim().eq_ignore_ascii_case("branch") {
+                Some(v.trim())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Identifies a message and its retransmissions: the same start
+    /// line, top `Via` branch and `CSeq`.
+    pub fn transaction_key(&self) -> (String, Option<&str>, Option<&str>) {
+        (
+            self.start_line().to_owned(),
+            self.via_branch(),
+            self.header("CSeq"),
+        )
+    }
+
+    /// The message as it went on the wire, with CRLF line endings and
+    /// binary body parts decoded from their hex dump.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for h in self.header.0.iter() {
+            bytes.extend_from_slice(h.as_bytes());
+            bytes.extend_from_slice(b"\r\n");
+        }
+        bytes.extend_from_slice(b"\r\n");
+        if let Some(ref body) = self.body {
+            body.write_bytes(&mut bytes);
+        }
+        bytes
+    }
+
+    /// Request-URI of a request.
+    pub fn request_uri(&self) -> Option<SipUri> {
+        self.method()?;
+        SipUri::parse(self.start_line().split_whitespace().nth(1)?)
+    }
+
+    pub fn from_uri(&self) -> Option<SipUri> {
+        SipUri::from_name_addr(self.header("From")?)
+    }
+
+    pub fn to_uri(&self) -> Option<SipUri> {
+        SipUri::from_name_addr(self.header("To")?)
+    }
+
+    /// `tag` parameter of the `From` header.
+    pub fn from_tag(&self) -> Option<&str> {
+        tag_param(self.header("From")?)
+    }
+
+    /// `tag` parameter of the `To` header, missing on the initial
+    /// request of a dialog.
+    pub fn to_tag(&self) -> Option<&str> {
+        tag_param(self.header("To")?)
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        matches!(self.framing, Framing::Truncated { .. })
+    }
+
+    /// SDP lines, if the message carried any.
+    pub fn sdp(&self) -> Option<&[String]> {
+        self.body
+            .as_ref()?
+            .parts()
+            .iter()
+            .find(|p| p.is_sdp())
+            .map(|p| p.lines())
+    }
+
+    /// Copy of the message with `f` applied to every line of text: the
+    /// log entry, start line, headers and body. ISUP parts are hex, only
+    /// their called and calling numbers go through `f`, on their own.
+    pub fn map_text<F: FnMut(&str) -> String>(&self, mut f: F) -> Self {
+        let log_entry = self
+            .log_entry
+            .as_ref()
+            .map(|e| LogEntry::parse(&f(&e.line)));
+        let header = SipHeader(self.header.0.iter().map(|l| f(l)).collect());
+
+        let body = self.body.as_ref().map(|b| {
+            let mut parts = Vec::with_capacity(b.parts.len());
+            for p in b.parts.iter() {
+                let headers = p.headers.iter().map(|l| f(l)).collect();
+                let lines = if p.is_isup() {
+                    let bytes = p.bytes();
+                    let mapped = isup::map_numbers(&bytes, &mut f);
+                    if mapped == bytes {
+                        p.lines.clone()
+                    } else {
+                        vec![isup::to_hex(&mapped)]
+                    }
+                } else {
+                    p.lines.iter().map(|l| f(l)).collect()
+                };
+                parts.push(BodyPart {
+                    content_type: p.content_type.clone(),
+                    headers,
+                    lines,
+                });
+            }
+            Body {
+                boundary: b.boundary.clone(),
+                parts,
+            }
+        });
+
+        Self {
+            header,
+            body,
+            framing: self.framing,
+            log_entry,
+        }
+    }
 }
 
 impl std::fmt::Display for SipPacket {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for h in self.header.0.iter() {
-            let _ = write!(f, "{}\n", h);
+            let _ = writeln!(f, "{}", h);
+        }
+
+        if let Some(body) = &self.body {
+            let _ = writeln!(f);
+            let _ = write!(f, "{}", body);
+            let _ = writeln!(f);
+        }
+
+        if self.framing != Framing::Complete {
+            let _ = writeln!(f, "{}", self.framing);
+        }
+
+        Ok(())
+    }
+}
+
+/// Message body, split into its parts for `multipart/*` content.
+#[derive(Debug, Clone)]
+pub struct Body {
+    boundary: Option<String>,
+    parts: Vec<BodyPart>,
+}
+
+impl Body {
+    fn parse(content_type: Option<&str>, lines: Vec<String>) -> Option<Self> {
+        if lines.is_empty() {
+            return None;
         }
 
-        if let Some(sdp) = &self.sdp {
-            let _ = write!(f, "\n");
-            for s in sdp.0.iter() {
-                let _ = write!(f, "{}\n", s);
+        let boundary = content_type
+            .filter(|ct| ct.to_ascii_lowercase().starts_with("multipart/"))
+            .and_then(|ct| content_type_param(ct, "boundary"));
+
+        let parts = match boundary {
+            Some(ref b) => split_multipart(b, lines),
+            None => vec![BodyPart {
+                content_type: content_type.map(|ct| ct.to_owned()),
+                headers: Vec::new(),
+                lines,
+            }],
+        };
+
+        Some(Self { boundary, parts })
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        let boundary = match self.boundary {
+            Some(ref b) => b,
+            None => {
+                for p in self.parts.iter() {
+                    bytes.extend_from_slice(&p.bytes());
+                    if !p.is_isup() {
+                        bytes.extend_from_slice(b"\r\n");
+                    }
+                }
+                return;
+            }
+        };
+
+        for p in self.parts.iter() {
+            bytes.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            for h in p.headers.iter() {
+                bytes.extend_from_slice(h.as_bytes());
+                bytes.extend_from_slice(b"\r\n");
+            }
+            bytes.extend_from_slice(b"\r\n");
+            bytes.extend_from_slice(&p.bytes());
+            bytes.extend_from_slice(b"\r\n");
+        }
+        bytes.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    }
+
+    /// A single part unless the body is `multipart/*`.
+    pub fn parts(&self) -> &[BodyPart] {
+        &self.parts
+    }
+}
+
+impl std::fmt::Display for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.boundary {
+            Some(ref b) => {
+                for p in self.parts.iter() {
+                    writeln!(f, "--{}", b)?;
+                    for h in p.headers.iter() {
+                        writeln!(f, "{}", h)?;
+                    }
+                    writeln!(f)?;
+                    write!(f, "{}", p)?;
+                }
+                writeln!(f, "--{}--", b)
+            }
+            None => self.parts.iter().try_for_each(|p| write!(f, "{}", p)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BodyPart {
+    content_type: Option<String>,
+    /// Part headers of a multipart body.
+    headers: Vec<String>,
+    lines: Vec<String>,
+}
+
+impl BodyPart {
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn is_sdp(&self) -> bool {
+        match self.content_type {
+            Some(ref ct) => has_media_type(ct, "application/sdp"),
+            None => self.lines.first().is_some_and(|l| l.starts_with("v=")),
+        }
+    }
+
+    pub fn is_isup(&self) -> bool {
+        self.content_type
+            .as_deref()
+            .is_some_and(|ct| has_media_type(ct, "application/isup"))
+    }
+
+    /// Raw content of the part. Binary parts, as ISUP, are logged
+    /// as hex and get decoded back.
+    pub fn bytes(&self) -> Vec<u8> {
+        let text = self.lines.join("\r\n");
+        if self.is_isup() {
+            if let Some(bytes) = isup::parse_hex(&text) {
+                return bytes;
+            }
+        }
+        text.into_bytes()
+    }
+
+    /// Decoded summary of a SIP-I ISUP part.
+    pub fn isup(&self) -> Option<IsupSummary> {
+        if self.is_isup() {
+            isup::decode(&self.bytes())
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for BodyPart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_isup() {
+            writeln!(f, "{}", isup::to_hex(&self.bytes()))?;
+            if let Some(summary) = self.isup() {
+                writeln!(f, "[{}]", summary)?;
             }
-            let _ = write!(f, "\n");
+            return Ok(());
         }
 
+        for l in self.lines.iter() {
+            writeln!(f, "{}", l)?;
+        }
         Ok(())
     }
 }
 
-#[derive(Debug)]
+/// Split the lines of a multipart body on `boundary`, dropping the
+/// preamble and epilogue.
+fn split_multipart(boundary: &str, lines: Vec<String>) -> Vec<BodyPart> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut current: Option<Vec<String>> = None;
+
+    for line in lines {
+        if line.trim_end().starts_with(&delimiter) {
+            if let Some(part) = current.take() {
+                parts.push(part);
+            }
+            if line.trim_end() == format!("{}--", delimiter) {
+                break;
+            }
+            current = Some(Vec::new());
+        } else if let Some(ref mut part) = current {
+            part.push(line);
+        }
+    }
+    // Unterminated last part
+    if let Some(part) = current {
+        parts.push(part);
+    }
+
+    parts
+        .into_iter()
+        .map(|mut lines| {
+            let split = lines.iter().position(|l| l.is_empty());
+            let content = match split {
+                Some(i) => lines.split_off(i + 1),
+                None => Vec::new(),
+            };
+            let mut headers = lines;
+            if split.is_some() {
+                headers.pop();
+            }
+
+            let mut content = content;
+            // The CRLF before the next delimiter belongs to it
+            if content.last().is_some_and(|l| l.is_empty()) {
+                content.pop();
+            }
+
+            BodyPart {
+                content_type: header_value(&headers, "Content-Type", Some("c"))
+                    .map(|ct| ct.to_owned()),
+                headers,
+                lines: content,
+            }
+        })
+        .collect()
+}
+
+fn has_media_type(content_type: &str, media_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|t| t.trim().eq_ignore_ascii_case(media_type))
+}
+
+fn tag_param(name_addr: &str) -> Option<&str> {
+    // Parameters after the closing `>`, or after the URI if there's
+    // none
+    let params = name_addr.rsplit_once('>').map_or(name_addr, |(_, p)| p);
+    params.split(';').skip(1).find_map(|p| {
+        let (n, v) = p.split_once('=')?;
+        if n.trim().eq_ignore_ascii_case("tag") {
+            Some(v.trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// Value of parameter `name` of a `Content-Type` header value.
+fn content_type_param(content_type: &str, name: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|p| {
+        let (n, v) = p.split_once('=')?;
+        if n.trim().eq_ignore_ascii_case(name) {
+            Some(v.trim().trim_matches('"').to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+/// Name of a header line, with compact forms expanded.
+pub fn header_name(line: &str) -> Option<&str> {
+    let name = line.split_once(':')?.0.trim();
+    let full = COMPACT_HEADERS
+        .iter()
+        .find(|(_, c)| c.eq_ignore_ascii_case(name))
+        .map(|(n, _)| *n);
+    Some(full.unwrap_or(name))
+}
+
+/// Value of the first header called `name` (or its compact form),
+/// case insensitive.
+fn header_value<'a>(
+    headers: &'a [String],
+    name: &str,
+    compact: Option<&str>,
+) -> Option<&'a str> {
+    headers.iter().find_map(|h| {
+        let (n, v) = h.split_once(':')?;
+        let n = n.trim();
+        if n.eq_ignore_ascii_case(name)
+            || compact.is_some_and(|c| n.eq_ignore_ascii_case(c))
+        {
+            Some(v.trim())
+        } else {
+            None
+        }
+    })
+}
+
+#[derive(Debug, Clone)]
 struct SipHeader(Vec<String>);
 
 impl SipHeader {
@@ -35,116 +512,352 @@ impl SipHeader {
         self.0.push(header)
     }
 
-    fn get_call_id(&self) -> Option<&String> {
-        self.0.iter().find(|&h| h.starts_with("Call-ID"))
+    fn value(&self, name: &str) -> Option<&str> {
+        let compact = COMPACT_HEADERS
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, c)| *c);
+        header_value(self.0.get(1..).unwrap_or(&[]), name, compact)
+    }
+
+    fn content_type(&self) -> Option<&str> {
+        self.value("Content-Type")
+    }
+
+    fn content_length(&self) -> Option<usize> {
+        self.value("Content-Length").and_then(|l| l.parse().ok())
     }
 }
 
-#[derive(Debug)]
-struct Sdp(Vec<String>);
+/// Whether the body is, or might be, SDP.
+fn is_sdp_content(h: &SipHeader) -> bool {
+    h.content_type()
+        .is_none_or(|ct| has_media_type(ct, "application/sdp"))
+}
 
 #[derive(Debug)]
 enum SipParseState {
     Idle,
-    InviteSipParse(SipHeader),
-    SipParse(SipHeader),
-    SdpParse(SipHeader, Sdp),
-    Done(SipPacket),
+    HeaderParse(SipHeader),
+    /// Headers, body lines so far and the body length they add up to.
+    BodyParse(SipHeader, Vec<String>, BodyLength),
+}
+
+impl Default for SipParser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[derive(Debug)]
 pub struct SipParser {
     state: SipParseState,
+    /// Most recent log entry line seen outside of a message.
+    last_entry: Option<LogEntry>,
+    /// Log entry of the message being parsed.
+    entry: Option<LogEntry>,
+    /// The trace kept the CRs of the lines ending in CRLF.
+    keeps_cr: bool,
+    /// The message being parsed ends its lines in CRLF.
+    crlf: bool,
+    packets: Vec<SipPacket>,
 }
 
 impl SipParser {
     pub fn new() -> Self {
         Self {
             state: SipParseState::Idle,
+            last_entry: None,
+            entry: None,
+            keeps_cr: false,
+            crlf: true,
+            packets: Vec::new(),
         }
     }
+
+    /// Pull the SIP messages of every call whose INVITE contains one
+    /// of `term` out of `trace`. No terms match every call.
     pub fn extract_sip(
-        mut self,
+        self,
         trace: &str,
         term: &[&str],
         _with_sdp: bool,
     ) -> Vec<SipPacket> {
+        self.extract(trace, &Filter::terms(term))
+    }
+
+    /// Pull the SIP messages of every call with a message matching
+    /// `filter` out of `trace`.
+    pub fn extract(self, trace: &str, filter: &Filter) -> Vec<SipPacket> {
+        let packets = self.parse(trace);
+
+        let mut call_ids: Vec<&str> = Vec::new();
+        for p in packets.iter().filter(|p| filter.matches(p)) {
+            if let Some(id) = p.call_id() {
+                if !call_ids.contains(&id) {
+                    call_ids.push(id);
+                }
+            }
+        }
+
+        let keep: Vec<bool> = packets
+            .iter()
+            .map(|p| match p.call_id() {
+                Some(id) => call_ids.contains(&id),
+                // Cut off before its Call-ID, it can only stand on
+                // its own
+                None => filter.matches(p),
+            })
+            .collect();
+        packets
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(p, keep)| if keep { Some(p) } else { None })
+            .collect()
+    }
+
+    /// Every SIP message in `trace`.
+    ///
+    /// The line ending a body is counted with is that of the headers:
+    /// CRLF when they keep their CRs, or when the trace doesn't keep
+    /// any, and LF otherwise.
+    pub fn parse(mut self, trace: &str) -> Vec<SipPacket> {
         use SipParseState::*;
 
-        println!("Searching for {:?} terms in SIP packets", term);
-        let mut packets = Vec::new();
-        let mut current_call_id: Option<String> = None;
-
-        for line in trace.lines() {
-            let state = match self.state {
-                Idle => {
-                    if line.contains("SIP/2.0") {
-                        if line.starts_with("INVITE") {
-                            InviteSipParse(SipHeader(vec![line.to_owned()]))
-                        } else {
-                            SipParse(SipHeader(vec![line.to_owned()]))
-                        }
-                    } else {
-                        Idle
-                    }
+        self.keeps_cr = trace.contains("\r\n");
+        for line in trace.split_inclusive('\n') {
+            let line = line.strip_suffix('\n').unwrap_or(line);
+            let (line, cr) = match line.strip_suffix('\r') {
+                Some(line) => (line, true),
+                None => (line, false),
+            };
+            let state = std::mem::replace(&mut self.state, Idle);
+            self.state = match state {
+                Idle => self.start(line, cr),
+                HeaderParse(h) if is_log_entry(line) => {
+                    // The log moved on before the end of the headers
+                    self.emit(h, Vec::new(), Framing::truncated_headers());
+                    self.start(line, cr)
                 }
-                InviteSipParse(mut h) => {
+                HeaderParse(mut h) => {
                     if line.is_empty() {
-                        // Here we should have a full INVITE SipPacket
-                        // Lets see if it matches the search terms
-                        // If not we go back to Idle state
-                        if h.0
-                            .iter()
-                            .any(|h| term.iter().any(|t| h.contains(t)))
-                        {
-                            current_call_id = h.get_call_id().cloned();
-                            SdpParse(h, Sdp(Vec::new()))
-                        } else {
-                            Idle
-                        }
+                        self.start_body(h)
                     } else {
+                        self.crlf |= cr;
                         h.add_header(line.to_owned());
-                        InviteSipParse(h)
+                        HeaderParse(h)
                     }
                 }
-                SipParse(mut h) => {
-                    if line.is_empty() {
-                        // If the call-id corressponds to
-                        // what we're currently looking for
-                        // then continue otherwise lets skip this one
-                        // and look for new packet
-                        if current_call_id.as_ref() == h.get_call_id() {
-                            SdpParse(h, Sdp(Vec::new()))
-                        } else {
+                BodyParse(h, body, length) if is_log_entry(line) => {
+                    // The log moved on before the end of the body
+                    let framing = length.framing(h.content_length());
+                    self.emit(h, body, framing);
+                    self.start(line, cr)
+                }
+                BodyParse(h, mut body, mut length) => {
+                    match h.content_length() {
+                        Some(declared)
+                            if line.is_empty() && length.len > declared =>
+                        {
+                            // Already past the declared length, the
+                            // blank line is what ends the message
+                            let framing = length.framing(Some(declared));
+                            self.emit(h, body, framing);
                             Idle
                         }
-                    } else {
-                        h.add_header(line.to_owned());
-                        SipParse(h)
-                    }
-                }
-                SdpParse(h, mut s) => {
-                    if line.is_empty() {
-                        Done(SipPacket {
-                            header: h,
-                            sdp: if s.0.len() > 0 { Some(s) } else { None },
-                        })
-                    } else if SDP_ATTRIBUTES.iter().any(|a| line.starts_with(a))
-                    {
-                        s.0.push(line.to_owned());
-                        SdpParse(h, s)
-                    } else {
-                        Idle
+                        Some(declared) => {
+                            body.push(line.to_owned());
+                            length.add(line);
+                            match length.framing(Some(declared)) {
+                                Framing::Complete => {
+                                    self.emit(h, body, Framing::Complete);
+                                    Idle
+                                }
+                                _ => BodyParse(h, body, length),
+                            }
+                        }
+                        None if line.is_empty() => {
+                            self.emit(h, body, Framing::Complete);
+                            Idle
+                        }
+                        None if is_sdp_content(&h)
+                            && !SDP_ATTRIBUTES
+                                .iter()
+                                .any(|a| line.starts_with(a)) =>
+                        {
+                            // No blank line after the body, the message
+                            // is complete and this line is something else
+                            self.emit(h, body, Framing::Complete);
+                            self.start(line, cr)
+                        }
+                        None => {
+                            body.push(line.to_owned());
+                            length.add(line);
+                            BodyParse(h, body, length)
+                        }
                     }
                 }
-                Done(p) => {
-                    packets.push(p);
-                    Idle
-                }
             };
+        }
 
-            self.state = state;
+        // Flush whatever the trace ended in the middle of
+        match std::mem::replace(&mut self.state, Idle) {
+            HeaderParse(h) => {
+                let framing = Framing::at_end_of_headers(&h);
+                self.emit(h, Vec::new(), framing);
+            }
+            BodyParse(h, body, length) => {
+                let framing = length.framing(h.content_length());
+                self.emit(h, body, framing);
+            }
+            Idle => {}
+        }
+
+        self.packets
+    }
+
+    /// State after seeing `line`, which ended in CRLF if `cr`, while
+    /// not inside a message.
+    fn start(&mut self, line: &str, cr: bool) -> SipParseState {
+        if is_log_entry(line) {
+            self.last_entry = Some(LogEntry::parse(line));
+            SipParseState::Idle
+        } else if line.contains("SIP/2.0") {
+            self.entry = self.last_entry.take();
+            self.crlf = cr || !self.keeps_cr;
+            SipParseState::HeaderParse(SipHeader(vec![line.to_owned()]))
+        } else {
+            SipParseState::Idle
+        }
+    }
+
+    /// State after the blank line ending the headers of a message.
+    fn start_body(&mut self, h: SipHeader) -> SipParseState {
+        if h.content_length() == Some(0) {
+            self.emit(h, Vec::new(), Framing::Complete);
+            SipParseState::Idle
+        } else {
+            SipParseState::BodyParse(h, Vec::new(), BodyLength::new(self.crlf))
+        }
+    }
+
+    fn emit(&mut self, header: SipHeader, body: Vec<String>, framing: Framing) {
+        let body = Body::parse(header.content_type(), body);
+        self.packets.push(SipPacket {
+            header,
+            body,
+            framing,
+            log_entry: self.entry.take(),
+        });
+    }
+}
+
+/// How well a logged message matches its declared length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    Complete,
+    /// The log ended or moved on before the end of the message.
+    /// `declared` is `None` when even the headers were cut short.
+    Truncated {
+        declared: Option<usize>,
+        logged: usize,
+    },
+    /// More body was logged than `Content-Length` declares.
+    LengthMismatch {
+        declared: usize,
+        logged: usize,
+    },
+}
+
+impl Framing {
+    fn truncated_headers() -> Self {
+        Framing::Truncated {
+            declared: None,
+            logged: 0,
+        }
+    }
+
+    /// Framing of a message whose headers were interrupted by the end
+    /// of the trace. `Content-Length` is usually the last header so
+    /// a bodyless message that has it is most likely complete.
+    fn at_end_of_headers(h: &SipHeader) -> Self {
+        match h.content_length() {
+            Some(0) => Framing::Complete,
+            Some(declared) => Framing::Truncated {
+                declared: Some(declared),
+                logged: 0,
+            },
+            None => Framing::truncated_headers(),
+        }
+    }
+}
+
+impl std::fmt::Display for Framing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Framing::Complete => Ok(()),
+            Framing::Truncated { declared: None, .. } => {
+                write!(f, "[truncated: headers incomplete]")
+            }
+            Framing::Truncated {
+                declared: Some(declared),
+                logged,
+            } => write!(
+                f,
+                "[truncated: {} of {} body bytes logged]",
+                logged, declared
+            ),
+            Framing::LengthMismatch { declared, logged } => write!(
+                f,
+                "[length mismatch: Content-Length {} but {} bytes logged]",
+                declared, logged
+            ),
+        }
+    }
+}
+
+/// Length of the body lines logged so far, each counted with the
+/// line ending of the message.
+#[derive(Debug)]
+struct BodyLength {
+    len: usize,
+    ending: usize,
+}
+
+impl BodyLength {
+    /// Lines ending in CRLF, or LF alone.
+    fn new(crlf: bool) -> Self {
+        Self {
+            len: 0,
+            ending: if crlf { 2 } else { 1 },
+        }
+    }
+
+    fn add(&mut self, line: &str) {
+        self.len += line.len() + self.ending;
+    }
+
+    /// `Content-Length` can match with or without a final line ending.
+    fn framing(&self, declared: Option<usize>) -> Framing {
+        let declared = match declared {
+            Some(d) => d,
+            None => return Framing::Complete,
+        };
+
+        if self.len == declared
+            || self.len.checked_sub(self.ending) == Some(declared)
+        {
+            Framing::Complete
+        } else if self.len.saturating_sub(self.ending) > declared {
+            Framing::LengthMismatch {
+                declared,
+                logged: self.len,
+            }
+        } else {
+            Framing::Truncated {
+                declared: Some(declared),
+                logged: self.len,
+            }
         }
-        packets
     }
 }
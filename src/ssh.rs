@@ -1,4 +1,5 @@
 use crate::error::{Error, TraceResult};
+use crate::executor::RemoteExecutor;
 use std::{
     net::{SocketAddr, TcpStream},
     thread,
@@ -19,13 +20,13 @@ impl SshClient {
     ) -> TraceResult<Self> {
         let timeout_start = Instant::now();
 
-        println!("Attempting connection...");
+        eprintln!("Attempting connection...");
         let tcp_connection = loop {
             match TcpStream::connect_timeout(&addr, Duration::from_secs(1)) {
                 Ok(tcp) => break tcp,
                 Err(e) => {
                     if timeout_start.elapsed() < TIMEOUT {
-                        println!("Retrying...");
+                        eprintln!("Retrying...");
                         thread::sleep(Duration::from_secs(1));
                     } else {
                         return Err(Error::SshConnection(e));
@@ -35,12 +36,10 @@ impl SshClient {
         };
 
         let mut ssh_session =
-            ssh2::Session::new().map_err(|e| Error::SshSession(e))?;
+            ssh2::Session::new().map_err(Error::SshSession)?;
 
         ssh_session.set_tcp_stream(tcp_connection);
-        ssh_session
-            .handshake()
-            .map_err(|e| Error::SshHandshake(e))?;
+        ssh_session.handshake().map_err(Error::SshHandshake)?;
 
         ssh_session
             .userauth_password(username, password)
@@ -77,8 +76,10 @@ impl SshClient {
 
         Ok(String::from_utf8_lossy(&out).to_string())
     }
+}
 
-    pub(crate) fn send_cmd(&mut self, command: &str) -> TraceResult<String> {
+impl RemoteExecutor for SshClient {
+    fn send_cmd(&mut self, command: &str) -> TraceResult<String> {
         use std::io::Read;
 
         let mut out = Vec::new();
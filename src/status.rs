@@ -0,0 +1,263 @@
+use crate::commands::ProcessInfo;
+use crate::error::{Error, TraceResult};
+use serde::Serialize;
+use std::fmt;
+
+/// Usage above this is reported as a failed health check.
+pub(crate) const MAX_LOG_DISK_USAGE: u8 = 90;
+
+const COLUMN_WIDTH: usize = 15;
+
+/// Snapshot of the remote host as shown by the `status` subcommand.
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub hostname: String,
+    pub uptime_secs: Option<u64>,
+    pub load: Option<LoadAverage>,
+    pub log_disk: Option<DiskUsage>,
+    pub processes: Vec<ProcessStatus>,
+    pub checks: Vec<HealthCheck>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadAverage {
+    pub one: f32,
+    pub five: f32,
+    pub fifteen: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiskUsage {
+    pub mount: String,
+    pub total_kb: u64,
+    pub used_kb: u64,
+    pub available_kb: u64,
+    pub used_percent: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProcessStatus {
+    pub owner: String,
+    pub pid: String,
+    pub process: String,
+    pub instance: Option<String>,
+    pub process_name: Option<String>,
+    pub uptime: String,
+    pub rss_kb: u64,
+    pub mem_percent: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl StatusReport {
+    pub fn new(hostname: String) -> Self {
+        Self {
+            hostname,
+            uptime_secs: None,
+            load: None,
+            log_disk: None,
+            processes: Vec::new(),
+            checks: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Render the report as pretty printed JSON.
+    pub fn to_json(&self) -> TraceResult<String> {
+        serde_json::to_string_pretty(self).map_err(Error::Serialize)
+    }
+
+    pub fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// Parse the concatenated output of `/proc/uptime` and
+    /// `/proc/loadavg`.
+    pub fn parse_proc_stats(&mut self, s: &str) {
+        let mut lines = s.lines();
+
+        self.uptime_secs = lines
+            .next()
+            .and_then(|l| l.split_whitespace().next())
+            .and_then(|u| u.parse::<f64>().ok())
+            .map(|u| u as u64);
+
+        self.load = lines.next().and_then(|l| {
+            let mut fields = l.split_whitespace().map(|f| f.parse().ok());
+            Some(LoadAverage {
+                one: fields.next()??,
+                five: fields.next()??,
+                fifteen: fields.next()??,
+            })
+        });
+
+        if self.uptime_secs.is_none() || self.load.is_none() {
+            self.warn(format!("Unexpected uptime/load output: {}", s.trim()));
+        }
+    }
+
+    /// Parse the output of `df -Pk <dir>`.
+    pub fn parse_disk_usage(&mut self, s: &str) {
+        // Filesystem 1024-blocks Used Available Capacity Mounted on
+        let usage = s.lines().nth(1).and_then(|l| {
+            let fields: Vec<_> = l.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            Some(DiskUsage {
+                mount: fields[5].to_owned(),
+                total_kb: fields[1].parse().ok()?,
+                used_kb: fields[2].parse().ok()?,
+                available_kb: fields[3].parse().ok()?,
+                used_percent: fields[4].trim_end_matches('%').parse().ok()?,
+            })
+        });
+
+        if usage.is_none() {
+            self.warn(format!("Unexpected df output: {}", s.trim()));
+        }
+        self.log_disk = usage;
+    }
+
+    pub fn add_process(&mut self, p: ProcessInfo) {
+        // The same process can show up under several grep terms
+        if self.processes.iter().any(|e| e.pid == p.pid) {
+            return;
+        }
+
+        self.processes.push(ProcessStatus {
+            owner: p.owner,
+            pid: p.pid,
+            process: p.process,
+            instance: p.instance,
+            process_name: p.process_name,
+            uptime: p.start,
+            rss_kb: p.rss,
+            mem_percent: p.mem_percent,
+        });
+    }
+
+    /// Derive the health checks from the collected data.
+    pub fn run_checks(&mut self) {
+        let mut checks = Vec::new();
+
+        checks.push(match self.log_disk {
+            Some(ref d) => HealthCheck {
+                name: "log disk usage".to_owned(),
+                ok: d.used_percent < MAX_LOG_DISK_USAGE,
+                detail: format!("{}% used on {}", d.used_percent, d.mount),
+            },
+            None => HealthCheck {
+                name: "log disk usage".to_owned(),
+                ok: false,
+                detail: "unknown".to_owned(),
+            },
+        });
+
+        checks.push(HealthCheck {
+            name: "cirpack processes".to_owned(),
+            ok: !self.processes.is_empty(),
+            detail: format!("{} running", self.processes.len()),
+        });
+
+        self.checks = checks;
+    }
+}
+
+impl fmt::Display for StatusReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = 6;
+        let width = columns * COLUMN_WIDTH + columns + 1;
+        let na = || "n/a".to_owned();
+
+        writeln!(f, "{:_>1$}", "_", width)?;
+        writeln!(
+            f,
+            "| {:<1$}|",
+            format!("Hostname: {}", self.hostname),
+            width - 3
+        )?;
+        writeln!(
+            f,
+            "| {:<1$}|",
+            format!(
+                "Uptime: {}  Load: {}",
+                self.uptime_secs.map_or_else(na, format_duration),
+                self.load.as_ref().map_or_else(na, |l| format!(
+                    "{:.2} {:.2} {:.2}",
+                    l.one, l.five, l.fifteen
+                ))
+            ),
+            width - 3
+        )?;
+        writeln!(
+            f,
+            "| {:<1$}|",
+            format!(
+                "/home/log: {}",
+                self.log_disk.as_ref().map_or_else(na, |d| format!(
+                    "{}% used, {} MB free",
+                    d.used_percent,
+                    d.available_kb / 1024
+                ))
+            ),
+            width - 3
+        )?;
+        writeln!(f, "{:_>1$}", "_", width)?;
+        writeln!(
+            f,
+            "|{1:^0$}|{2:^0$}|{3:^0$}|{4:^0$}|{5:^0$}|{6:^0$}|",
+            COLUMN_WIDTH,
+            "Process #",
+            "Process name",
+            "Instance",
+            "Instance name",
+            "Uptime",
+            "Memory (MB)",
+        )?;
+        for p in self.processes.iter() {
+            writeln!(
+                f,
+                "|{1:^0$}|{2:^0$}|{3:^0$}|{4:^0$}|{5:^0$}|{6:^0$}|",
+                COLUMN_WIDTH,
+                p.pid,
+                p.process,
+                p.instance.as_deref().unwrap_or("n/a"),
+                p.process_name.as_deref().unwrap_or("n/a"),
+                p.uptime,
+                p.rss_kb / 1024,
+            )?;
+        }
+        writeln!(f, "{:_>1$}", "_", width)?;
+
+        for c in self.checks.iter() {
+            writeln!(
+                f,
+                "[{}] {}: {}",
+                if c.ok { " OK " } else { "FAIL" },
+                c.name,
+                c.detail
+            )?;
+        }
+        for w in self.warnings.iter() {
+            writeln!(f, "Warning: {}", w)?;
+        }
+        Ok(())
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    format!(
+        "{}d {:02}:{:02}:{:02}",
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
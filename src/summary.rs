@@ -0,0 +1,252 @@
+use crate::log_entry::TimeOfDay;
+use crate::sip_parse::SipPacket;
+use std::{collections::HashSet, fmt, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Party {
+    Caller,
+    Callee,
+}
+
+impl fmt::Display for Party {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Party::Caller => write!(f, "caller"),
+            Party::Callee => write!(f, "callee"),
+        }
+    }
+}
+
+/// Outcome and timing of a single call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallSummary {
+    pub call_id: String,
+    pub caller: Option<String>,
+    pub callee: Option<String>,
+    pub invite_time: Option<TimeOfDay>,
+    /// First 18x to the INVITE.
+    pub ringing_time: Option<TimeOfDay>,
+    /// 200 OK to the INVITE.
+    pub answer_time: Option<TimeOfDay>,
+    /// BYE or CANCEL.
+    pub end_method: Option<String>,
+    pub end_time: Option<TimeOfDay>,
+    pub ended_by: Option<Party>,
+    /// Final response to the INVITE.
+    pub final_status: Option<u16>,
+    pub final_status_text: Option<String>,
+    pub reason: Option<String>,
+    pub warning: Option<String>,
+    pub retransmissions: usize,
+}
+
+impl CallSummary {
+    fn new(call_id: &str) -> Self {
+        Self {
+            call_id: call_id.to_owned(),
+            caller: None,
+            callee: None,
+            invite_time: None,
+            ringing_time: None,
+            answer_time: None,
+            end_method: None,
+            end_time: None,
+            ended_by: None,
+            final_status: None,
+            final_status_text: None,
+            reason: None,
+            warning: None,
+            retransmissions: 0,
+        }
+    }
+
+    /// Post dial delay: INVITE to the first 18x.
+    pub fn pdd(&self) -> Option<Duration> {
+        Some(self.ringing_time?.since(self.invite_time?))
+    }
+
+    /// Answer to BYE.
+    pub fn duration(&self) -> Option<Duration> {
+        Some(self.end_time?.since(self.answer_time?))
+    }
+
+    pub fn answered(&self) -> bool {
+        self.answer_time.is_some()
+    }
+}
+
+/// Summarise every call in `packets`, in the order they started.
+pub fn summarize(packets: &[SipPacket]) -> Vec<CallSummary> {
+    let mut call_ids: Vec<&str> = Vec::new();
+    for id in packets.iter().filter_map(|p| p.call_id()) {
+        if !call_ids.contains(&id) {
+            call_ids.push(id);
+        }
+    }
+
+    call_ids
+        .into_iter()
+        .map(|id| {
+            let call: Vec<_> =
+                packets.iter().filter(|p| p.call_id() == Some(id)).collect();
+            summarize_call(id, &call)
+        })
+        .collect()
+}
+
+fn summarize_call(call_id: &str, packets: &[&SipPacket]) -> CallSummary {
+    let mut summary = CallSummary::new(call_id);
+    let mut seen = HashSet::new();
+
+    let invite = packets.iter().find(|p| p.method() == Some("INVITE"));
+    // Sent again with a new CSeq after a 401/407 challenge
+    let initial: HashSet<u32> = packets
+        .iter()
+        .filter(|p| p.method() == Some("INVITE") && p.to_tag().is_none())
+        .filter_map(|p| p.cseq())
+        .map(|(n, _)| n)
+        .collect();
+    let caller_tag = invite.and_then(|i| i.from_tag());
+
+    if let Some(invite) = invite {
+        summary.caller = invite.from_uri().and_then(|u| u.user);
+        summary.callee = invite.to_uri().and_then(|u| u.user);
+        summary.invite_time = invite.time();
+    }
+
+    for p in packets.iter() {
+        if !seen.insert(p.transaction_key()) {
+            summary.retransmissions += 1;
+            continue;
+        }
+
+        let to_invite = p.cseq().is_some_and(|(n, m)| {
+            m.eq_ignore_ascii_case("INVITE") && initial.contains(&n)
+        });
+
+        match (p.method(), p.status_code()) {
+            // The challenge was answered, so it wasn't the outcome
+            (Some("INVITE"), _)
+                if p.to_tag().is_none()
+                    && matches!(
+                        summary.final_status,
+                        Some(401) | Some(407)
+                    ) =>
+            {
+                summary.final_status = None;
+                summary.final_status_text = None;
+                summary.warning = None;
+            }
+            (Some(m), _)
+                if (m == "BYE" || m == "CANCEL")
+                    && summary.end_method.is_none() =>
+            {
+                summary.end_method = Some(m.to_owned());
+                summary.end_time = p.time();
+                summary.ended_by =
+                    Some(if m == "CANCEL" || p.from_tag() == caller_tag {
+                        Party::Caller
+                    } else {
+                        Party::Callee
+                    });
+                if summary.reason.is_none() {
+                    summary.reason = p.header("Reason").map(|r| r.to_owned());
+                }
+            }
+            (None, Some(code))
+                if to_invite
+                    && (180..190).contains(&code)
+                    && summary.ringing_time.is_none() =>
+            {
+                summary.ringing_time = p.time();
+            }
+            (None, Some(code))
+                if to_invite
+                    && code >= 200
+                    && summary.final_status.is_none() =>
+            {
+                summary.final_status = Some(code);
+                summary.final_status_text =
+                    p.start_line().splitn(3, ' ').nth(2).map(|t| t.to_owned());
+                if (200..300).contains(&code) {
+                    summary.answer_time = p.time();
+                }
+                // The final response explains the outcome better than
+                // whatever the BYE/CANCEL said
+                if let Some(reason) = p.header("Reason") {
+                    summary.reason = Some(reason.to_owned());
+                }
+                summary.warning = p.header("Warning").map(|w| w.to_owned());
+            }
+            _ => {}
+        }
+    }
+
+    summary
+}
+
+impl fmt::Display for CallSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let na = || "n/a".to_owned();
+        let time = |t: Option<TimeOfDay>| t.map_or_else(na, |t| t.to_string());
+        let secs =
+            |d: Duration| format!("{}.{:03}s", d.as_secs(), d.subsec_millis());
+
+        writeln!(f, "Call-ID:         {}", self.call_id)?;
+        writeln!(
+            f,
+            "Caller:          {}",
+            self.caller.as_deref().unwrap_or("n/a")
+        )?;
+        writeln!(
+            f,
+            "Callee:          {}",
+            self.callee.as_deref().unwrap_or("n/a")
+        )?;
+        writeln!(f, "INVITE:          {}", time(self.invite_time))?;
+        writeln!(
+            f,
+            "Ringing:         {}{}",
+            time(self.ringing_time),
+            self.pdd()
+                .map_or_else(String::new, |d| format!(" (PDD {})", secs(d)))
+        )?;
+        writeln!(f, "Answered:        {}", time(self.answer_time))?;
+        match self.end_method {
+            Some(ref m) => writeln!(
+                f,
+                "Ended:           {} by {} ({})",
+                time(self.end_time),
+                self.ended_by.map_or_else(na, |p| p.to_string()),
+                m
+            )?,
+            None => writeln!(f, "Ended:           n/a")?,
+        }
+        writeln!(
+            f,
+            "Duration:        {}",
+            self.duration().map_or_else(na, secs)
+        )?;
+        writeln!(
+            f,
+            "Final status:    {}",
+            match self.final_status {
+                Some(code) => format!(
+                    "{} {}",
+                    code,
+                    self.final_status_text.as_deref().unwrap_or("")
+                )
+                .trim_end()
+                .to_owned(),
+                None => na(),
+            }
+        )?;
+        if let Some(ref reason) = self.reason {
+            writeln!(f, "Reason:          {}", reason)?;
+        }
+        if let Some(ref warning) = self.warning {
+            writeln!(f, "Warning:         {}", warning)?;
+        }
+        writeln!(f, "Retransmissions: {}", self.retransmissions)
+    }
+}
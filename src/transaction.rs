@@ -0,0 +1,176 @@
+//! Retransmissions and unanswered transactions.
+
+use crate::log_entry::TimeOfDay;
+use crate::sip_parse::SipPacket;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+/// A message together with the retransmissions of it that followed.
+#[derive(Debug)]
+pub struct Message {
+    pub packet: SipPacket,
+    /// Times of the retransmissions, in the order they were logged.
+    pub retransmissions: Vec<Option<TimeOfDay>>,
+}
+
+impl Message {
+    /// `[retransmitted N times, last at HH:MM:SS.mmm]`, if it was.
+    pub fn retransmission_note(&self) -> Option<String> {
+        let n = self.retransmissions.len();
+        if n == 0 {
+            return None;
+        }
+
+        let plural = if n == 1 { "" } else { "s" };
+        Some(match self.retransmissions.last().copied().flatten() {
+            Some(last) => {
+                format!(
+                    "[retransmitted {} time{}, last at {}]",
+                    n, plural, last
+                )
+            }
+            None => format!("[retransmitted {} time{}]", n, plural),
+        })
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.packet)?;
+        match self.retransmission_note() {
+            Some(note) => writeln!(f, "{}", note),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Fold every retransmission, i.e. a message with the same start line,
+/// `Via` branch and `CSeq` as an earlier one, into the first copy.
+pub fn collapse_retransmissions(packets: Vec<SipPacket>) -> Vec<Message> {
+    let mut messages: Vec<Message> = Vec::new();
+    // Index in `messages` of the first copy of each message
+    let mut firsts: HashMap<RetransmissionKey, usize> = HashMap::new();
+
+    for packet in packets {
+        let (start_line, branch, cseq) = packet.transaction_key();
+        let key = (
+            packet.call_id().map(str::to_owned),
+            start_line,
+            branch.map(str::to_owned),
+            cseq.map(str::to_owned),
+        );
+        match firsts.get(&key) {
+            Some(&i) => messages[i].retransmissions.push(packet.time()),
+            None => {
+                firsts.insert(key, messages.len());
+                messages.push(Message {
+                    packet,
+                    retransmissions: Vec::new(),
+                });
+            }
+        }
+    }
+
+    messages
+}
+
+/// Call-ID and [`SipPacket::transaction_key`].
+type RetransmissionKey =
+    (Option<String>, String, Option<String>, Option<String>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// A request that never got a final response.
+    NoFinalResponse,
+    /// A final response to an INVITE that was never ACKed.
+    NoAck(u16),
+}
+
+/// A transaction that didn't complete within the capture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionIssue {
+    pub call_id: String,
+    /// Start line of the request.
+    pub request: String,
+    pub time: Option<TimeOfDay>,
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for TransactionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.call_id, self.request)?;
+        if let Some(time) = self.time {
+            write!(f, " at {}", time)?;
+        }
+        match self.kind {
+            IssueKind::NoFinalResponse => write!(f, ": no final response"),
+            IssueKind::NoAck(code) => {
+                write!(f, ": {} response was never ACKed", code)
+            }
+        }
+    }
+}
+
+/// Find the requests without a final response and the INVITE final
+/// responses without an ACK.
+pub fn find_issues(packets: &[SipPacket]) -> Vec<TransactionIssue> {
+    // First final response of each transaction, and the Call-ID and
+    // `CSeq` number of every ACK
+    let mut final_status = HashMap::new();
+    let mut acked = HashSet::new();
+    for p in packets.iter() {
+        match p.status_code() {
+            Some(code) if code >= 200 => {
+                final_status.entry(transaction_id(p)).or_insert(code);
+            }
+            _ => {}
+        }
+        if p.method() == Some("ACK") {
+            acked.insert(ack_id(p));
+        }
+    }
+
+    let mut issues = Vec::new();
+    let mut seen = HashSet::new();
+    for request in packets.iter() {
+        let method = match request.method() {
+            Some(m) if m != "ACK" => m,
+            _ => continue,
+        };
+        if !seen.insert(transaction_id(request)) {
+            continue;
+        }
+
+        let kind = match final_status.get(&transaction_id(request)) {
+            None => IssueKind::NoFinalResponse,
+            Some(&code)
+                if method == "INVITE" && !acked.contains(&ack_id(request)) =>
+            {
+                IssueKind::NoAck(code)
+            }
+            Some(_) => continue,
+        };
+
+        issues.push(TransactionIssue {
+            call_id: request.call_id().unwrap_or_default().to_owned(),
+            request: request.start_line().to_owned(),
+            time: request.time(),
+            kind,
+        });
+    }
+
+    issues
+}
+
+/// Call-ID, top `Via` branch and `CSeq`, shared by a request and its
+/// responses.
+fn transaction_id(p: &SipPacket) -> (Option<&str>, Option<&str>, Option<&str>) {
+    (p.call_id(), p.via_branch(), p.header("CSeq"))
+}
+
+/// Call-ID and `CSeq` number, shared by an INVITE and its ACK.
+fn ack_id(p: &SipPacket) -> (Option<&str>, Option<u32>) {
+    (p.call_id(), p.cseq().map(|(n, _)| n))
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+
+/// The parts of a `sip:`, `sips:` or `tel:` URI we care about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SipUri {
+    pub scheme: String,
+    pub user: Option<String>,
+    /// Empty for `tel:` URIs.
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl SipUri {
+    /// Parse a bare URI, i.e. `sip:+35311234567@10.1.1.1:5060;user=phone`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (scheme, rest) = s.trim().split_once(':')?;
+        let scheme = scheme.to_ascii_lowercase();
+        // URI parameters and headers aren't interesting here
+        let rest = rest.split([';', '?']).next()?;
+
+        if scheme == "tel" {
+            return Some(Self {
+                scheme,
+                user: Some(rest.to_owned()),
+                host: String::new(),
+                port: None,
+            });
+        }
+        if scheme != "sip" && scheme != "sips" {
+            return None;
+        }
+
+        let (user, hostport) = match rest.rsplit_once('@') {
+            Some((u, h)) => (Some(u.to_owned()), h),
+            None => (None, rest),
+        };
+        let (host, port) = split_host_port(hostport);
+
+        Some(Self {
+            scheme,
+            user,
+            host: host.to_owned(),
+            port,
+        })
+    }
+
+    /// Parse the URI out of a `From`/`To`/`Contact` style header
+    /// value, i.e. `"Alice" <sip:alice@10.1.1.1>;tag=1234`.
+    pub fn from_name_addr(s: &str) -> Option<Self> {
+        match (s.find('<'), s.find('>')) {
+            (Some(start), Some(end)) if start < end => {
+                Self::parse(&s[start + 1..end])
+            }
+            _ => Self::parse(s.split(';').next()?),
+        }
+    }
+}
+
+impl fmt::Display for SipUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.scheme)?;
+        if let Some(ref user) = self.user {
+            write!(f, "{}", user)?;
+            if !self.host.is_empty() {
+                write!(f, "@")?;
+            }
+        }
+        write!(f, "{}", self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+fn split_host_port(s: &str) -> (&str, Option<u16>) {
+    // IPv6 reference, i.e. [2001:db8::1]:5060
+    if let Some(rest) = s.strip_prefix('[') {
+        if let Some((host, port)) = rest.split_once(']') {
+            let port = port.strip_prefix(':').and_then(|p| p.parse().ok());
+            return (host, port);
+        }
+    }
+
+    match s.rsplit_once(':') {
+        Some((host, port)) => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (s, None),
+        },
+        None => (s, None),
+    }
+}
@@ -0,0 +1,134 @@
+//! `--since`/`--until` time windows.
+
+use crate::error::{Error, TraceResult};
+use crate::log_entry::{LogBlock, TimeOfDay};
+use crate::sip_parse::SipPacket;
+use std::time::Duration;
+
+/// Either end of a [`TimeWindow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBound {
+    /// Time of day, i.e. `10:15:20`.
+    At(TimeOfDay),
+    /// Offset from the start of the capture, i.e. `+90s` or `+1m30s`.
+    AfterStart(Duration),
+}
+
+impl TimeBound {
+    pub fn parse(s: &str) -> TraceResult<Self> {
+        let bound = match s.strip_prefix('+') {
+            Some(offset) => parse_duration(offset).map(TimeBound::AfterStart),
+            None => TimeOfDay::parse(s).map(TimeBound::At),
+        };
+        bound.ok_or_else(|| Error::InvalidTime(s.to_owned()))
+    }
+
+    /// Parse the end of a window. A time to the minute, i.e. `10:31`,
+    /// takes in the whole of that minute.
+    pub fn parse_until(s: &str) -> TraceResult<Self> {
+        match Self::parse(s)? {
+            TimeBound::At(t) if s.matches(':').count() == 1 => {
+                Ok(TimeBound::At(TimeOfDay::from_millis(t.millis() + 59_999)))
+            }
+            bound => Ok(bound),
+        }
+    }
+
+    /// Offset from `start` in milliseconds. Times up to 12 hours
+    /// before `start` are before the capture rather than on the next
+    /// day.
+    fn offset(self, start: TimeOfDay) -> i64 {
+        match self {
+            TimeBound::At(t) => signed_offset(t, start),
+            TimeBound::AfterStart(d) => d.as_millis() as i64,
+        }
+    }
+}
+
+fn signed_offset(t: TimeOfDay, start: TimeOfDay) -> i64 {
+    const HALF_DAY: i64 = 12 * 60 * 60 * 1000;
+    let offset = t.since(start).as_millis() as i64;
+    if offset > HALF_DAY {
+        offset - 2 * HALF_DAY
+    } else {
+        offset
+    }
+}
+
+/// Parse `90`, `90s`, `2m`, `1m30s`, `1h` or `500ms`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    if s.is_empty() {
+        return None;
+    }
+    if let Ok(secs) = s.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let mut total = Duration::default();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let n: u64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        total += match &rest[..unit_len] {
+            "h" => Duration::from_secs(n * 3600),
+            "m" => Duration::from_secs(n * 60),
+            "s" => Duration::from_secs(n),
+            "ms" => Duration::from_millis(n),
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+    }
+    Some(total)
+}
+
+/// Messages and log blocks between `since` and `until`, both
+/// inclusive. Relative bounds count from the first timestamp of the
+/// capture, and so does the wrap around midnight.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub since: Option<TimeBound>,
+    pub until: Option<TimeBound>,
+}
+
+impl TimeWindow {
+    pub fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    /// Whether `time` falls in the window of a capture that started at
+    /// `start`. Untimed entries always do.
+    pub fn contains(&self, time: Option<TimeOfDay>, start: TimeOfDay) -> bool {
+        let offset = match time {
+            Some(t) => signed_offset(t, start),
+            None => return true,
+        };
+        self.since.is_none_or(|s| offset >= s.offset(start))
+            && self.until.is_none_or(|u| offset <= u.offset(start))
+    }
+
+    pub fn packets(
+        &self,
+        packets: Vec<SipPacket>,
+        start: TimeOfDay,
+    ) -> Vec<SipPacket> {
+        packets
+            .into_iter()
+            .filter(|p| self.contains(p.time(), start))
+            .collect()
+    }
+
+    pub fn blocks<'a>(
+        &self,
+        blocks: Vec<LogBlock<'a>>,
+        start: TimeOfDay,
+    ) -> Vec<LogBlock<'a>> {
+        blocks
+            .into_iter()
+            .filter(|b| self.contains(b.time, start))
+            .collect()
+    }
+}
@@ -0,0 +1,117 @@
+use cirtrace::{pcap, Bundle, CaptureInfo, Redactor};
+use flate2::read::GzDecoder;
+use std::{collections::HashMap, fs, io::Read};
+
+mod common;
+use common::{packets, scratch};
+
+#[test]
+fn pcap_has_a_record_per_message() {
+    let packets = packets("call");
+    let pcap = pcap::write(&packets, Some(1_760_832_000));
+
+    assert_eq!(&pcap[..4], 0xa1b2_c3d4u32.to_le_bytes());
+    // LINKTYPE_RAW
+    assert_eq!(&pcap[20..24], 101u32.to_le_bytes());
+
+    let mut records = Vec::new();
+    let mut rest = &pcap[24..];
+    while !rest.is_empty() {
+        let field = |i: usize| {
+            u32::from_le_bytes([rest[i], rest[i + 1], rest[i + 2], rest[i + 3]])
+        };
+        let (secs, micros, len) = (field(0), field(4), field(8) as usize);
+        records.push((secs, micros, rest[16..16 + len].to_vec()));
+        rest = &rest[16 + len..];
+    }
+    assert_eq!(records.len(), packets.len());
+
+    // 10:30:00.500, the INVITE retransmission
+    let (secs, micros, ip) = &records[1];
+    assert_eq!((*secs, *micros), (1_760_832_000 + 37_800, 500_000));
+    assert_eq!(ip[0], 0x45);
+    assert_eq!(&ip[12..20], [10, 1, 1, 1, 10, 2, 2, 2]);
+    assert_eq!(u16::from_be_bytes([ip[2], ip[3]]) as usize, ip.len());
+    // UDP ports, then the payload
+    assert_eq!(&ip[20..24], [0x13, 0xc4, 0x13, 0xc4]);
+    assert!(ip[28..].starts_with(b"INVITE sip:+35319876543@10.2.2.2"));
+
+    // The IPv4 header checksums to zero
+    let sum: u32 = ip[..20]
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]) as u32)
+        .sum();
+    assert_eq!((sum & 0xffff) + (sum >> 16), 0xffff);
+}
+
+#[test]
+fn redaction_is_consistent_and_keeps_lengths() {
+    let mut redactor = Redactor::new().country_code("353");
+    let a = redactor.text("From: <sip:+353871234567@10.1.1.1>;tag=1");
+    let b = redactor.text("To: <tel:0871234567>");
+    let c = redactor.text("Contact: <sip:+353861111111@10.1.1.1:5060>");
+
+    assert_eq!(a, "From: <sip:+353870000001@10.1.1.1>;tag=1");
+    assert_eq!(b, "To: <tel:0870000001>");
+    assert_eq!(c, "Contact: <sip:+353860000002@10.1.1.1:5060>");
+    // Times, addresses and branches stay as they are
+    let line = "10:30:00.000 SIP RECV from 10.1.1.1:5060 z9hG4bK1234567";
+    assert_eq!(redactor.text(line), line);
+
+    assert_eq!(
+        redactor.mapping(),
+        [
+            ("+353871234567".to_owned(), "+353870000001".to_owned()),
+            ("0871234567".to_owned(), "0870000001".to_owned()),
+            ("+353861111111".to_owned(), "+353860000002".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn redacted_packets_keep_their_framing() {
+    let mut redactor = Redactor::new();
+    for packet in packets("call") {
+        let redacted = packet.map_text(|l| redactor.text(l));
+        assert_eq!(redacted.to_bytes().len(), packet.to_bytes().len());
+        assert_eq!(redacted.framing(), packet.framing());
+        assert_eq!(redacted.time(), packet.time());
+        assert!(!redacted.to_string().contains("19876543"));
+    }
+}
+
+#[test]
+fn archive_has_the_files_and_a_manifest() {
+    let path = scratch("bundle").join("ticket-42.tar.gz");
+
+    let info = CaptureInfo {
+        host: Some("192.168.1.100".to_owned()),
+        ..Default::default()
+    };
+    let mut bundle = Bundle::new(info).redacted(true);
+    bundle.add("raw.log", "Log as fetched from the host", "log\n");
+    bundle.add("sip.txt", "Matched SIP messages", "INVITE\n");
+    bundle.write(&path, false).unwrap();
+
+    let mut files = HashMap::new();
+    let gz = GzDecoder::new(fs::File::open(&path).unwrap());
+    for entry in tar::Archive::new(gz).entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents).unwrap();
+        files.insert(name, contents);
+    }
+
+    assert_eq!(files["ticket-42/raw.log"], "log\n");
+    assert_eq!(files["ticket-42/sip.txt"], "INVITE\n");
+    let manifest: serde_json::Value =
+        serde_json::from_str(&files["ticket-42/manifest.json"]).unwrap();
+    assert_eq!(manifest["redacted"], true);
+    assert_eq!(manifest["capture"]["host"], "192.168.1.100");
+    assert_eq!(manifest["files"][1]["name"], "sip.txt");
+    assert_eq!(manifest["files"][1]["size"], 7);
+
+    // Never clobbered by default
+    assert!(bundle.write(&path, false).is_err());
+}
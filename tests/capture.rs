@@ -0,0 +1,255 @@
+use cirtrace::{
+    pick_process, CaptureSession, CommandRunner, Error, LocalExecutor,
+    ModuleSelector, ModuleTypes, RemoteExecutor, ScriptedExecutor, SipParser,
+};
+use std::time::Duration;
+
+const PS_IBCF: &str = include_str!("fixtures/ps_ibcf.txt");
+const IBCF_CORE_LOG: &str = include_str!("fixtures/ibcf_core.log");
+const EXITED_OK: &str = "@@cirtrace:exit 0\n";
+
+fn scripted_sbc() -> ScriptedExecutor {
+    ScriptedExecutor::new()
+        .on("ps aux", PS_IBCF)
+        .on("date", "10:15 1760865300 +0100\n")
+        .on("mgt_cscf", EXITED_OK)
+        .on("tail -n", IBCF_CORE_LOG)
+}
+
+#[test]
+fn sip_capture_of_a_single_call() {
+    let executor = scripted_sbc();
+    let history = executor.history();
+    let runner = CommandRunner::with_executor(executor);
+
+    let selector = ModuleSelector {
+        module_name: Some("ibcf_core"),
+        ..Default::default()
+    };
+    let candidates = runner.find_processes("ibcf_core").unwrap();
+    let process = pick_process(&selector, candidates, false).unwrap();
+    assert_eq!(process.pid, "28848");
+    assert_eq!(process.instance.as_deref(), Some("1"));
+    assert_eq!(
+        process.config_file.as_deref(),
+        Some("/home/etc/ibcf_core.cfg")
+    );
+
+    let session = CaptureSession::start(&runner, process).unwrap();
+    let clock = session.start_clock();
+    assert_eq!(clock.time, "10:15");
+    assert_eq!(clock.unix_time, Some(1_760_865_300));
+    assert_eq!(clock.utc_offset, Some(3600));
+    let log = session.finish().unwrap();
+
+    let packets = SipParser::new().extract_sip(&log, &["+35311234567"], true);
+    let start_lines: Vec<_> = packets.iter().map(|p| p.start_line()).collect();
+    assert_eq!(
+        start_lines,
+        [
+            "INVITE sip:+35319876543@10.2.2.2:5060 SIP/2.0",
+            "SIP/2.0 100 Trying",
+            "SIP/2.0 180 Ringing",
+            "SIP/2.0 200 OK",
+            "ACK sip:+35319876543@10.2.2.2:5060 SIP/2.0",
+            "BYE sip:+35319876543@10.2.2.2:5060 SIP/2.0",
+            "SIP/2.0 200 OK",
+        ]
+    );
+    assert!(packets
+        .iter()
+        .all(|p| p.call_id() == Some("call-a@10.1.1.1")));
+    assert_eq!(packets[0].sdp().map(|s| s.len()), Some(8));
+    assert_eq!(packets[1].sdp(), None);
+
+    let history = history.borrow();
+    assert_eq!(
+        history.as_slice(),
+        [
+            "ps aux |grep ibcf_core ",
+            "mgt_cscf -name=ibcf -i1 -debug=3 -loglevel=0 2>&1; echo \
+             @@cirtrace:exit $?",
+            "date \"+%H:%M %s %z\"",
+            "tail -n +$(grep -m 1 -n 10:15 /home/log/ibcf_core.1 | cut -d':' \
+             -f 1) /home/log/ibcf_core.1",
+            "mgt_cscf -name=ibcf -i1 -debug=0 -loglevel=1 2>&1; echo \
+             @@cirtrace:exit $?",
+        ]
+    );
+}
+
+#[test]
+fn ambiguous_module_lists_candidates() {
+    let runner = CommandRunner::with_executor(scripted_sbc());
+
+    let selector = ModuleSelector {
+        module: Some("ibcf"),
+        ..Default::default()
+    };
+    let candidates = runner.find_processes("ibcf").unwrap();
+    assert_eq!(candidates.len(), 2);
+
+    match pick_process(&selector, candidates, false) {
+        Err(Error::AmbiguousModule(c)) => {
+            assert_eq!(c.len(), 2);
+            assert!(c[0].contains("ibcf_core"));
+            assert!(c[1].contains("ibcf_border"));
+        }
+        r => panic!("expected ambiguous module, got {:?}", r),
+    }
+}
+
+#[test]
+fn instance_narrows_module() {
+    let runner = CommandRunner::with_executor(scripted_sbc());
+
+    let selector = ModuleSelector {
+        module: Some("ibcf"),
+        instance: Some("2"),
+        ..Default::default()
+    };
+    let candidates = runner.find_processes("ibcf").unwrap();
+    let process = pick_process(&selector, candidates, false).unwrap();
+    assert_eq!(process.process_name.as_deref(), Some("ibcf_border"));
+}
+
+#[test]
+fn unknown_command_is_an_error() {
+    let runner = CommandRunner::with_executor(ScriptedExecutor::new());
+
+    match runner.get_remote_time() {
+        Err(Error::UnexpectedCommand(c)) => assert!(c.starts_with("date")),
+        r => panic!("expected unexpected command, got {:?}", r),
+    }
+}
+
+#[test]
+fn local_executor_runs_shell() {
+    let out = LocalExecutor.send_cmd("echo 10:15").unwrap();
+    assert_eq!(out, "10:15\n");
+}
+
+fn ibcf_core(runner: &CommandRunner) -> cirtrace::ProcessInfo {
+    let selector = ModuleSelector {
+        module_name: Some("ibcf_core"),
+        ..Default::default()
+    };
+    let candidates = runner.find_processes("ibcf_core").unwrap();
+    pick_process(&selector, candidates, false).unwrap()
+}
+
+#[test]
+fn debug_is_disabled_when_the_fetch_fails() {
+    // No answer to the `tail`
+    let executor = ScriptedExecutor::new()
+        .on("ps aux", PS_IBCF)
+        .on("date", "10:15\n")
+        .on("nohup", "4242\n")
+        .on("kill", "")
+        .on("mgt_cscf", EXITED_OK);
+    let history = executor.history();
+    let runner = CommandRunner::with_executor(executor);
+
+    let mut session =
+        CaptureSession::start(&runner, ibcf_core(&runner)).unwrap();
+    session.watchdog(Duration::from_secs(75)).unwrap();
+    assert!(session.finish().is_err());
+
+    let history = history.borrow();
+    assert!(history[3].starts_with("nohup sh -c 'sleep 75; mgt_cscf"));
+    assert_eq!(
+        &history[history.len() - 2..],
+        [
+            "mgt_cscf -name=ibcf -i1 -debug=0 -loglevel=1 2>&1; echo \
+             @@cirtrace:exit $?",
+            "kill 4242"
+        ]
+    );
+}
+
+#[test]
+fn debug_is_disabled_when_dropped() {
+    let executor = scripted_sbc();
+    let history = executor.history();
+    let runner = CommandRunner::with_executor(executor);
+
+    let session = CaptureSession::start(&runner, ibcf_core(&runner)).unwrap();
+    drop(session);
+
+    assert_eq!(
+        history.borrow().last().unwrap(),
+        "mgt_cscf -name=ibcf -i1 -debug=0 -loglevel=1 2>&1; echo \
+         @@cirtrace:exit $?"
+    );
+}
+
+#[test]
+fn failing_to_enable_debug_is_an_error() {
+    let runner = CommandRunner::with_executor(
+        ScriptedExecutor::new()
+            .on("ps aux", PS_IBCF)
+            .on("mgt_cscf", "unknown instance 1\n@@cirtrace:exit 2\n"),
+    );
+
+    let err = CaptureSession::start(&runner, ibcf_core(&runner)).err();
+    match err {
+        Some(Error::CommandFailed(_, status, out)) => {
+            assert_eq!(status, "2");
+            assert_eq!(out, "unknown instance 1");
+        }
+        e => panic!("expected a failed command, got {:?}", e),
+    }
+}
+
+#[test]
+fn file_paths_are_quoted() {
+    let executor = ScriptedExecutor::new().on("cat", "Port=5060\n");
+    let history = executor.history();
+    let runner = CommandRunner::with_executor(executor);
+
+    let config = runner.read_file("/home/etc/it's; rm.cfg").unwrap();
+    assert_eq!(config, "Port=5060\n");
+    assert_eq!(history.borrow()[0], r"cat '/home/etc/it'\''s; rm.cfg'");
+}
+
+#[test]
+fn more_module_types_from_a_file() {
+    const PS_GW_SIP: &str = "omni 3100 0.0 1.2 90804 94432 ? Sl 2018 0:50 \
+                             /home/omni/bin/gw_sip -i2 -rgw_sip_a\n";
+    let types = ModuleTypes::parse(
+        r#"[{
+            "process": "gw_sip",
+            "enable_debug": "gw_debug {process} {instance} on",
+            "disable_debug": "gw_debug {process} {instance} off",
+            "log_file": "/home/log/{name}.log"
+        }]"#,
+    )
+    .unwrap();
+    assert_eq!(types.names(), ["ibcf", "bgcf", "gw_sip"]);
+    assert!(ModuleTypes::parse(r#"[{"process": "gw_sip"}]"#).is_err());
+
+    let executor = ScriptedExecutor::new()
+        .on("ps aux", PS_GW_SIP)
+        .on("date", "10:15\n")
+        .on("gw_debug", EXITED_OK)
+        .on("tail -n", "");
+    let history = executor.history();
+    let runner = CommandRunner::with_executor(executor);
+    let gw_sip = runner.find_processes("gw_sip").unwrap().remove(0);
+    assert!(matches!(
+        CaptureSession::start(&runner, gw_sip.clone()).err(),
+        Some(Error::UnsupportedModule(_))
+    ));
+
+    let runner = runner.module_types(types);
+    CaptureSession::start(&runner, gw_sip)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let history = history.borrow();
+    assert_eq!(
+        history[1],
+        "gw_debug gw_sip 2 on 2>&1; echo @@cirtrace:exit $?"
+    );
+    assert!(history[3].ends_with(" /home/log/gw_sip_a.log"));
+}
@@ -0,0 +1,37 @@
+//! Fixture loaders and scratch directories shared by the integration
+//! tests.
+
+// Each test crate only uses some of them
+#![allow(dead_code)]
+
+use cirtrace::{SipPacket, SipParser};
+use std::{env, fs, path::PathBuf};
+
+/// Path of `tests/fixtures/sip/<name>`.
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/sip")
+        .join(name)
+}
+
+/// Contents of `tests/fixtures/sip/<name>.log`.
+pub fn log(name: &str) -> String {
+    fs::read_to_string(fixture(&format!("{}.log", name))).unwrap()
+}
+
+/// Every SIP message of `tests/fixtures/sip/<name>.log`.
+pub fn packets(name: &str) -> Vec<SipPacket> {
+    SipParser::new().extract_sip(&log(name), &[], true)
+}
+
+/// Fresh scratch directory for one test.
+pub fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!(
+        "cirtrace-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,168 @@
+use cirtrace::{e164, Error, Filter, SipParser};
+
+mod common;
+
+fn call_ids(name: &str, terms: &[&str]) -> Vec<String> {
+    selected(name, &Filter::parse_terms(terms).unwrap())
+}
+
+fn selected(name: &str, filter: &Filter) -> Vec<String> {
+    let log = common::log(name);
+
+    let mut ids: Vec<String> = Vec::new();
+    for p in SipParser::new().extract(&log, filter) {
+        let id = p.call_id().unwrap().to_owned();
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+#[test]
+fn plain_terms_are_a_shorthand() {
+    assert_eq!(call_ids("interleaved", &["+35318888888"]), ["call-x"]);
+    assert_eq!(
+        call_ids("interleaved", &["+35318888888", "+35317777777"]),
+        ["call-x", "call-2"]
+    );
+    assert_eq!(call_ids("interleaved", &[]).len(), 3);
+}
+
+#[test]
+fn terms_not_starting_with_a_field_are_plain() {
+    assert_eq!(call_ids("interleaved", &["tag=f-call-x"]), ["call-x"]);
+    assert_eq!(
+        call_ids("interleaved", &["<sip:+35317777777@10.1.1.1>"]),
+        ["call-2"]
+    );
+    let filter = Filter::parse_terms(&["not (status>=400)"]).unwrap();
+    assert!(matches!(filter, Filter::Not(_)), "{:?}", filter);
+}
+
+#[test]
+fn field_comparisons() {
+    assert_eq!(call_ids("interleaved", &["status>=400"]), ["call-2"]);
+    assert_eq!(
+        call_ids("interleaved", &["from.user=+3531*"]),
+        ["call-1", "call-x", "call-2"]
+    );
+    assert_eq!(call_ids("interleaved", &["from.user=*8888*"]), ["call-x"]);
+    assert_eq!(call_ids("interleaved", &["to.host=10.2.2.2"]).len(), 3);
+    assert_eq!(call_ids("interleaved", &["call-id=CALL-1"]), ["call-1"]);
+    assert_eq!(
+        call_ids("interleaved", &["header[Reason]~\"cause=1[0-9]\""]),
+        ["call-2"]
+    );
+}
+
+#[test]
+fn boolean_operators() {
+    assert_eq!(
+        call_ids(
+            "interleaved",
+            &["method=INVITE and ruri.user=+35319876543 and not call-id=call-2"]
+        ),
+        ["call-1"]
+    );
+    assert_eq!(
+        call_ids("interleaved", &["(status=486 or status=180) and cseq<2"]),
+        ["call-1", "call-2"]
+    );
+    assert_eq!(
+        call_ids(
+            "multipart",
+            &["method=INVITE and header[Content-Type]~\"^multipart/\""]
+        )
+        .len(),
+        1
+    );
+}
+
+#[test]
+fn invalid_expressions() {
+    for expr in &[
+        "status>=abc",
+        "nosuchfield=1",
+        "(method=INVITE",
+        "header[Reason]~\"(\"",
+        "method=",
+        "method=INVITE and",
+    ] {
+        match Filter::parse(expr) {
+            Err(Error::InvalidFilter(_)) => {}
+            other => panic!("{} parsed as {:?}", expr, other),
+        }
+    }
+}
+
+#[test]
+fn plain_terms_for_highlighting() {
+    let filter = Filter::parse("3531234 or (status>=400 and \"10.1.1.1\")");
+    assert_eq!(filter.unwrap().plain_terms(), ["3531234", "10.1.1.1"]);
+}
+
+#[test]
+fn e164_normalisation() {
+    let n = |s| e164::normalize(s, "353");
+    assert_eq!(n("+353871234567").as_deref(), Some("+353871234567"));
+    assert_eq!(n("00353871234567").as_deref(), Some("+353871234567"));
+    assert_eq!(n("0871234567").as_deref(), Some("+353871234567"));
+    assert_eq!(n("353871234567").as_deref(), Some("+353871234567"));
+    assert_eq!(n("tel:+353-87-123-4567").as_deref(), Some("+353871234567"));
+    assert_eq!(n("1234").as_deref(), Some("1234"));
+    assert_eq!(n("alice"), None);
+
+    assert!(e164::is_phone_number("+353 87 123-4567"));
+    assert!(e164::is_phone_number("087.123.4567"));
+    assert!(!e164::is_phone_number("1234"));
+    assert!(!e164::is_phone_number("192.168.100.200"));
+    assert!(!e164::is_phone_number("192.168.100"));
+    assert!(!e164::is_phone_number("call-123456"));
+
+    assert!(e164::is_country_code("353"));
+    assert!(!e164::is_country_code("0353"));
+}
+
+#[test]
+fn search_regex() {
+    let re = |p| Filter::regex(p).unwrap();
+    assert_eq!(selected("numbers", &re(r"^\+44")), ["call-n3"]);
+    assert_eq!(selected("numbers", &re(r"^10\.1\.1\.9$")), ["call-n2"]);
+    assert_eq!(selected("numbers", &re("^call-n1$")), ["call-n1"]);
+    // Without normalisation the national forms don't match
+    assert_eq!(
+        selected("numbers", &re(r"^\+353871234567$")),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        selected("numbers", &re(r"^\+353871234567$").with_e164("353")),
+        ["call-n1", "call-n2"]
+    );
+    assert_eq!(
+        selected("numbers", &re(r"^\+35317654321$").with_e164("353")),
+        ["call-n2"]
+    );
+    assert!(matches!(Filter::regex("("), Err(Error::InvalidFilter(_))));
+}
+
+#[test]
+fn plain_number_in_any_form() {
+    let filter = Filter::parse_terms(&["00353871234567"]).unwrap();
+    assert_eq!(selected("numbers", &filter), Vec::<String>::new());
+    assert_eq!(
+        selected("numbers", &filter.with_e164("353")),
+        ["call-n1", "call-n2"]
+    );
+
+    // Not phone numbers, matched as they are
+    for term in ["10.1.1.1", "1234"].iter() {
+        let filter = Filter::parse_terms(&[term]).unwrap().with_e164("353");
+        assert!(matches!(filter, Filter::Term(_)), "{:?}", filter);
+    }
+
+    let filter = Filter::parse_terms(&["017654321"])
+        .unwrap()
+        .with_e164("353");
+    assert_eq!(selected("numbers", &filter), ["call-n2"]);
+}
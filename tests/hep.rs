@@ -0,0 +1,140 @@
+use cirtrace::hep::{encode, parse_url};
+use cirtrace::{Endpoints, Error, HepSender, LogEntry, SipParser};
+use std::{
+    collections::HashMap, convert::TryInto, net::UdpSocket, time::Duration,
+};
+
+mod common;
+use common::packets;
+
+/// Chunks of a HEPv3 packet by type.
+fn chunks(hep: &[u8]) -> HashMap<u16, Vec<u8>> {
+    assert_eq!(&hep[..4], b"HEP3");
+    assert_eq!(u16::from_be_bytes([hep[4], hep[5]]) as usize, hep.len());
+
+    let mut chunks = HashMap::new();
+    let mut rest = &hep[6..];
+    while !rest.is_empty() {
+        assert_eq!(&rest[..2], [0, 0]);
+        let chunk_type = u16::from_be_bytes([rest[2], rest[3]]);
+        let len = u16::from_be_bytes([rest[4], rest[5]]) as usize;
+        chunks.insert(chunk_type, rest[6..len].to_vec());
+        rest = &rest[len..];
+    }
+    chunks
+}
+
+#[test]
+fn encodes_addresses_time_and_correlation_id() {
+    let invite = &packets("call")[0];
+    let hep = encode(invite, Duration::from_millis(1_700_000_000_250), 42);
+    let c = chunks(&hep);
+
+    assert_eq!(c[&0x0001], [2]);
+    assert_eq!(c[&0x0002], [17]);
+    assert_eq!(c[&0x0003], [10, 1, 1, 1]);
+    assert_eq!(c[&0x0004], [10, 2, 2, 2]);
+    assert_eq!(c[&0x0007], 5060u16.to_be_bytes());
+    assert_eq!(c[&0x0008], 5060u16.to_be_bytes());
+    assert_eq!(c[&0x0009], 1_700_000_000u32.to_be_bytes());
+    assert_eq!(c[&0x000a], 250_000u32.to_be_bytes());
+    assert_eq!(c[&0x000b], [1]);
+    assert_eq!(c[&0x000c], 42u32.to_be_bytes());
+    assert_eq!(c[&0x0011], b"call-c");
+
+    let payload = String::from_utf8(c[&0x000f].clone()).unwrap();
+    assert!(payload.starts_with("INVITE sip:+35319876543@10.2.2.2 SIP/2.0\r\n"));
+    assert!(payload.ends_with("Content-Length: 0\r\n\r\n"));
+}
+
+#[test]
+fn payload_keeps_the_body() {
+    let invite = &packets("interleaved")[0];
+    let payload = String::from_utf8(invite.to_bytes()).unwrap();
+    let (head, sdp) = payload.split_at(payload.find("\r\n\r\n").unwrap() + 4);
+
+    assert!(sdp.starts_with("v=0\r\n"));
+    assert!(sdp.ends_with("\r\n"));
+    assert!(head.contains(&format!("Content-Length: {}\r\n", sdp.len())));
+}
+
+#[test]
+fn endpoints_share_an_address_family() {
+    let entry = LogEntry::parse(
+        "10:30:00.000 [ibcf_core] SIP RECV from [::ffff:10.1.1.1]:5060 to \
+         10.2.2.2:5061",
+    );
+    assert_eq!(
+        entry.endpoints(),
+        Endpoints::V4(
+            "10.1.1.1:5060".parse().unwrap(),
+            "10.2.2.2:5061".parse().unwrap()
+        )
+    );
+    let entry = LogEntry::parse("10:30:00.000 [ibcf_core] SIP RECV");
+    assert_eq!(entry.endpoints(), Endpoints::default());
+}
+
+#[test]
+fn long_messages_are_cut_short() {
+    let log = common::log("call").replacen(
+        "Call-ID: call-c\n",
+        &format!("Call-ID: call-c\nX-Padding: {}\n", "x".repeat(70_000)),
+        1,
+    );
+    let invite = &SipParser::new().parse(&log)[0];
+    let hep = encode(invite, Duration::from_secs(1_700_000_000), 0);
+    assert_eq!(hep.len(), 65507);
+
+    let c = chunks(&hep);
+    assert_eq!(c[&0x0011], b"call-c");
+    assert!(invite.to_bytes().starts_with(&c[&0x000f]));
+}
+
+#[test]
+fn sends_to_a_udp_listener() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    listener
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let url = format!("udp://{}", listener.local_addr().unwrap());
+
+    let packets = packets("call");
+    let sent = HepSender::connect(&url)
+        .unwrap()
+        .day_start(1_700_000_000 - 1_700_000_000 % 86400)
+        .send_all(&packets)
+        .unwrap();
+    assert_eq!(sent, 8);
+
+    let mut buf = [0u8; 65535];
+    let mut payloads = Vec::new();
+    for _ in 0..sent {
+        let (len, _) = listener.recv_from(&mut buf).unwrap();
+        let c = chunks(&buf[..len]);
+        let secs = u32::from_be_bytes(c[&0x0009][..].try_into().unwrap());
+        // 10:30:xx on the given day
+        assert_eq!((secs % 86400) / 60, 10 * 60 + 30);
+        payloads.push(String::from_utf8(c[&0x000f].clone()).unwrap());
+    }
+    assert!(payloads[6].starts_with("BYE "));
+}
+
+#[test]
+fn urls() {
+    assert_eq!(
+        parse_url("udp://127.0.0.1:9061").unwrap(),
+        "127.0.0.1:9061".parse().unwrap()
+    );
+    assert_eq!(
+        parse_url("udp://127.0.0.1").unwrap(),
+        "127.0.0.1:9060".parse().unwrap()
+    );
+    assert_eq!(
+        parse_url("udp://[::1]:9060").unwrap(),
+        "[::1]:9060".parse().unwrap()
+    );
+    for url in &["tcp://127.0.0.1:9060", "udp://", "127.0.0.1:9060"] {
+        assert!(matches!(parse_url(url), Err(Error::InvalidHepUrl(_))));
+    }
+}
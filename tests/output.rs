@@ -0,0 +1,68 @@
+use cirtrace::{Error, Output, WriteMode};
+use std::fs;
+
+mod common;
+use common::scratch;
+
+#[test]
+fn separates_items() {
+    let path = scratch("output-separate").join("out.txt");
+    Output::new(&path)
+        .separator("\n")
+        .save(&["INVITE\n", "100 Trying"])
+        .unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "INVITE\n\n100 Trying\n");
+}
+
+#[test]
+fn refuses_to_clobber_by_default() {
+    let path = scratch("output-clobber").join("out.txt");
+    fs::write(&path, "previous run\n").unwrap();
+
+    let err = Output::new(&path).save(&["new"]).unwrap_err();
+    assert!(matches!(err, Error::OutputExists(p) if p == path));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "previous run\n");
+}
+
+#[test]
+fn checks_before_saving() {
+    let path = scratch("output-check").join("out.txt");
+    assert!(Output::new(&path).check().is_ok());
+
+    fs::write(&path, "previous run\n").unwrap();
+    let err = Output::new(&path).check().unwrap_err();
+    assert!(matches!(err, Error::OutputExists(p) if p == path));
+    assert!(Output::new(&path).mode(WriteMode::Append).check().is_ok());
+    assert!(Output::new("-").check().is_ok());
+}
+
+#[test]
+fn overwrite_truncates() {
+    let dir = scratch("output-overwrite");
+    let path = dir.join("out.txt");
+    fs::write(&path, "a much longer previous run\n").unwrap();
+
+    Output::new(&path)
+        .mode(WriteMode::Overwrite)
+        .save(&["short"])
+        .unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "short\n");
+    // No temporary file left behind
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+}
+
+#[test]
+fn append_separates_runs() {
+    let path = scratch("output-append").join("out.txt");
+    fs::write(&path, "first run").unwrap();
+
+    Output::new(&path)
+        .mode(WriteMode::Append)
+        .separator("\n")
+        .save(&["second run"])
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "first run\n\nsecond run\n"
+    );
+}
@@ -0,0 +1,68 @@
+use cirtrace::{CommandRunner, HostHealth, Limits, ScriptedExecutor};
+use std::time::Duration;
+
+const PROBES: &str = "@@cirtrace:proc
+350735.12 1360000.50
+3.10 2.50 2.00 2/512 12345
+@@cirtrace:nproc
+4
+@@cirtrace:df
+Filesystem     1024-blocks     Used Available Capacity Mounted on
+/dev/sda5         20511312 14360000   5102648      74% /home/log
+";
+
+fn health(probes: &str) -> HostHealth {
+    let runner = CommandRunner::with_executor(
+        ScriptedExecutor::new().on("nproc", probes),
+    );
+    runner.host_health().unwrap()
+}
+
+#[test]
+fn healthy_host() {
+    let health = health(PROBES);
+    assert_eq!(health.cpus, Some(4));
+    assert_eq!(health.log_disk.as_ref().unwrap().available_kb, 5_102_648);
+
+    let limits = Limits::default();
+    assert!(health.problems(&limits, Duration::from_secs(15)).is_empty());
+    assert_eq!(
+        health.problems(&limits, Duration::from_secs(900)),
+        ["trace time of 900s is over the maximum of 600s"]
+    );
+}
+
+#[test]
+fn full_disk_and_busy_host() {
+    let probes = PROBES
+        .replace("3.10 2.50", "6.40 5.90")
+        .replace("5102648      74%", "  802648      96%");
+    let health = health(&probes);
+    let problems = health.problems(&Limits::default(), Duration::from_secs(15));
+    assert_eq!(
+        problems,
+        [
+            "96% used on /home/log",
+            "load of 6.40 is over 4.00 for 4 CPUs"
+        ]
+    );
+
+    let limits = Limits {
+        max_disk_usage: 100,
+        ..Default::default()
+    };
+    assert_eq!(
+        health.problems(&limits, Duration::from_secs(15))[0],
+        "only 783 MB free on /home/log"
+    );
+}
+
+#[test]
+fn unreadable_figures_are_problems() {
+    let problems =
+        HostHealth::default().problems(&Limits::default(), Duration::ZERO);
+    assert_eq!(
+        problems,
+        ["free space on /home/log unknown", "load unknown"]
+    );
+}
@@ -0,0 +1,166 @@
+use cirtrace::{
+    CaptureSession, CommandRunner, Filter, ProcessInfo, Progress,
+    RemoteExecutor, TraceResult,
+};
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+const CALL_LOG: &str = include_str!("fixtures/sip/call.log");
+
+/// A log file that grows between polls.
+struct GrowingLog(Rc<RefCell<String>>);
+
+impl RemoteExecutor for GrowingLog {
+    fn send_cmd(&mut self, command: &str) -> TraceResult<String> {
+        let log = self.0.borrow();
+        let args: Vec<_> = command.split_whitespace().collect();
+        Ok(match args[0] {
+            "date" => "10:30\n".to_owned(),
+            "stat" => format!("{}\n", log.len()),
+            "tail" => {
+                let offset: usize = args[2][1..].parse().unwrap();
+                let len: usize = args[7].parse().unwrap();
+                log[offset - 1..offset - 1 + len].to_owned()
+            }
+            _ => panic!("unexpected command {}", command),
+        })
+    }
+}
+
+fn process() -> ProcessInfo {
+    ProcessInfo {
+        owner: "omni".to_owned(),
+        pid: "28848".to_owned(),
+        mem_percent: "0.1".to_owned(),
+        rss: 0,
+        start: "10:00".to_owned(),
+        process: "ibcf".to_owned(),
+        instance: None,
+        process_name: Some("ibcf_core".to_owned()),
+        config_file: None,
+    }
+}
+
+#[test]
+fn follows_the_matching_calls() {
+    let log = Rc::new(RefCell::new("10:29:59.000 earlier stuff\n".to_owned()));
+    let runner = CommandRunner::with_executor(GrowingLog(Rc::clone(&log)));
+    let session = CaptureSession::start(&runner, process()).unwrap();
+    let filter = Filter::terms(&["+35319876543"]);
+    let mut progress = Progress::new(Duration::from_secs(15)).follow(&filter);
+
+    progress.poll(&session).unwrap();
+    assert_eq!(progress.log_growth(), Some(0));
+    assert_eq!(progress.invites(), 0);
+
+    // Up to the 180, then the rest of the call
+    let ringing = CALL_LOG.find("10:30:05.000").unwrap();
+    log.borrow_mut().push_str(&CALL_LOG[..ringing]);
+    progress.poll(&session).unwrap();
+    assert_eq!(progress.log_growth(), Some(ringing as u64));
+    assert_eq!(progress.invites(), 1);
+    assert_eq!(progress.latest_status(), Some("180 Ringing"));
+
+    log.borrow_mut().push_str(&CALL_LOG[ringing..]);
+    progress.poll(&session).unwrap();
+    assert_eq!(progress.log(), CALL_LOG);
+    assert_eq!(progress.latest_status(), Some("200 OK"));
+
+    let line = progress.to_string();
+    assert!(line.starts_with("0s elapsed, 15s left, log +"), "{}", line);
+    assert!(
+        line.ends_with(", 1 INVITE, latest 200 OK, call ended"),
+        "{}",
+        line
+    );
+}
+
+#[test]
+fn only_the_size_without_following() {
+    let log = Rc::new(RefCell::new(String::new()));
+    let runner = CommandRunner::with_executor(GrowingLog(Rc::clone(&log)));
+    let session = CaptureSession::start(&runner, process()).unwrap();
+    let mut progress = Progress::new(Duration::from_secs(15));
+
+    progress.poll(&session).unwrap();
+    log.borrow_mut().push_str(&"x".repeat(48_200));
+    progress.poll(&session).unwrap();
+
+    assert_eq!(progress.log(), "");
+    assert!(progress.to_string().ends_with("s left, log +48.2 kB"));
+}
+
+#[test]
+fn done_once_the_call_ends() {
+    let log = Rc::new(RefCell::new(String::new()));
+    let runner = CommandRunner::with_executor(GrowingLog(Rc::clone(&log)));
+    let session = CaptureSession::start(&runner, process()).unwrap();
+    let filter = Filter::terms(&[]);
+    let mut progress = Progress::new(Duration::from_secs(15))
+        .follow(&filter)
+        .until_call_ends(Duration::ZERO);
+    progress.poll(&session).unwrap();
+
+    // Answered, then hung up
+    let bye = CALL_LOG.find("BYE sip:").unwrap();
+    log.borrow_mut().push_str(&CALL_LOG[..bye]);
+    progress.poll(&session).unwrap();
+    assert!(!progress.is_done());
+
+    log.borrow_mut().push_str(&CALL_LOG[bye..]);
+    progress.poll(&session).unwrap();
+    assert!(progress.call_ended());
+    assert!(progress.is_done());
+    assert!(progress.to_string().ends_with(", call ended"));
+}
+
+#[test]
+fn a_failed_reinvite_does_not_end_the_call() {
+    let log = Rc::new(RefCell::new(String::new()));
+    let runner = CommandRunner::with_executor(GrowingLog(Rc::clone(&log)));
+    let session = CaptureSession::start(&runner, process()).unwrap();
+    let filter = Filter::terms(&[]);
+    let mut progress = Progress::new(Duration::from_secs(15))
+        .follow(&filter)
+        .until_call_ends(Duration::ZERO);
+    progress.poll(&session).unwrap();
+
+    // Answered, then a re-INVITE in the dialog is turned down
+    let bye = CALL_LOG.find("10:30:47.300").unwrap();
+    let invite = &CALL_LOG[..CALL_LOG.find("10:30:00.500").unwrap()];
+    let reinvite = invite
+        .replace("10:30:00.000", "10:30:20.000")
+        .replace("10.2.2.2>", "10.2.2.2>;tag=bob")
+        .replace("10 INVITE", "11 INVITE");
+    let refused = reinvite.replace("SIP RECV", "SIP SEND").replace(
+        "INVITE sip:+35319876543@10.2.2.2 SIP/2.0",
+        "SIP/2.0 491 Request Pending",
+    );
+    log.borrow_mut().push_str(&CALL_LOG[..bye]);
+    log.borrow_mut().push_str(&reinvite);
+    log.borrow_mut().push_str(&refused);
+    progress.poll(&session).unwrap();
+    assert_eq!(progress.latest_status(), Some("491 Request Pending"));
+    assert!(!progress.call_ended());
+}
+
+#[test]
+fn a_message_split_across_polls_counts_once() {
+    let log = Rc::new(RefCell::new(String::new()));
+    let runner = CommandRunner::with_executor(GrowingLog(Rc::clone(&log)));
+    let session = CaptureSession::start(&runner, process()).unwrap();
+    let filter = Filter::terms(&["+35319876543"]);
+    let mut progress = Progress::new(Duration::from_secs(15)).follow(&filter);
+    progress.poll(&session).unwrap();
+
+    // Cut in the middle of the headers of the first INVITE
+    let cut = CALL_LOG.find("Call-ID").unwrap();
+    log.borrow_mut().push_str(&CALL_LOG[..cut]);
+    progress.poll(&session).unwrap();
+    log.borrow_mut().push_str(&CALL_LOG[cut..]);
+    progress.poll(&session).unwrap();
+    assert_eq!(progress.invites(), 1);
+    assert_eq!(progress.latest_status(), Some("200 OK"));
+
+    let followed = progress.followed_log().unwrap();
+    assert_eq!(session.finish_followed(followed).unwrap(), CALL_LOG);
+}
@@ -0,0 +1,102 @@
+use cirtrace::{CaptureInfo, Error, RawCapture, SipParser};
+use std::{
+    fs,
+    time::{Duration, UNIX_EPOCH},
+};
+
+mod common;
+use common::scratch;
+
+fn capture() -> RawCapture {
+    let info = CaptureInfo {
+        version: "0.1.10".to_owned(),
+        host: Some("192.168.1.100".to_owned()),
+        module: Some("ibcf".to_owned()),
+        module_name: Some("ibcf_border".to_owned()),
+        instance: Some("1".to_owned()),
+        config_file: None,
+        start: Some(UNIX_EPOCH + Duration::from_secs(1_760_869_800)),
+        end: Some(UNIX_EPOCH + Duration::from_secs(1_760_869_815)),
+        remote_start: Some("10:30".to_owned()),
+        remote_unix_time: None,
+        utc_offset: None,
+    };
+    RawCapture::new(info, common::log("call"))
+}
+
+#[test]
+fn round_trips_plain_and_gzipped() {
+    let dir = scratch("raw-round-trip");
+    let raw = capture();
+
+    for name in &["call.raw", "call.raw.gz"] {
+        let path = dir.join(name);
+        raw.save(&path, false).unwrap();
+        assert_eq!(RawCapture::load(&path).unwrap(), raw);
+    }
+    // Actually compressed
+    let gz = fs::read(dir.join("call.raw.gz")).unwrap();
+    assert_eq!(&gz[..2], [0x1f, 0x8b]);
+}
+
+#[test]
+fn header_and_summary() {
+    let raw = capture();
+    let text = raw.to_text();
+    assert!(text.starts_with(
+        "# cirtrace raw log\n\
+         # version: 0.1.10\n\
+         # host: 192.168.1.100\n\
+         # module: ibcf\n\
+         # module-name: ibcf_border\n\
+         # instance: 1\n\
+         # start: 1760869800\n\
+         # end: 1760869815\n\
+         # remote-start: 10:30\n\
+         \n"
+    ));
+    assert_eq!(
+        raw.info.to_string(),
+        "ibcf_border (ibcf -i1) on 192.168.1.100 for 15s, \
+         captured by cirtrace 0.1.10"
+    );
+}
+
+#[test]
+fn dated_on_the_remote_day() {
+    let mut info = capture().info;
+    // 10:30 UTC without the remote clock
+    assert_eq!(info.day_start(), Some(1_760_832_000));
+
+    // 10:15 at +0100, the day started at 23:00 UTC
+    info.remote_unix_time = Some(1_760_865_300);
+    info.utc_offset = Some(3600);
+    assert_eq!(info.day_start(), Some(1_760_828_400));
+    let text = RawCapture::new(info.clone(), String::new()).to_text();
+    assert_eq!(RawCapture::parse(&text).info, info);
+}
+
+#[test]
+fn bare_logs_parse_the_same() {
+    let raw = capture();
+    let bare = RawCapture::parse(&raw.log);
+    assert_eq!(bare.info, CaptureInfo::default());
+    assert_eq!(bare.log, raw.log);
+
+    let reloaded = RawCapture::parse(&raw.to_text());
+    let parse =
+        |log: &str| SipParser::new().extract_sip(log, &["call-c"], true);
+    assert_eq!(parse(&reloaded.log).len(), parse(&raw.log).len());
+    assert_eq!(parse(&reloaded.log).len(), 8);
+}
+
+#[test]
+fn refuses_to_clobber() {
+    let path = scratch("raw-clobber").join("call.raw");
+    fs::write(&path, "keep me").unwrap();
+
+    let err = capture().save(&path, false).unwrap_err();
+    assert!(matches!(err, Error::OutputExists(_)));
+    capture().save(&path, true).unwrap();
+    assert_eq!(RawCapture::load(&path).unwrap(), capture());
+}
@@ -0,0 +1,77 @@
+use cirtrace::{Redactor, SipParser};
+
+mod common;
+use common::log;
+
+#[test]
+fn users_and_tel_separators() {
+    let mut redactor = Redactor::new();
+    let a = redactor.text("From: \"Alice\" <sip:alice@example.com>;tag=1");
+    let b = redactor.text("To: <tel:+1-212-555-0100>");
+    let c = redactor.text("Contact: <sips:alice@10.1.1.1>");
+
+    assert_eq!(a, "From: \"Alice\" <sip:u0001@example.com>;tag=1");
+    assert_eq!(b, "To: <tel:+1-212-000-0001>");
+    assert_eq!(c, "Contact: <sips:u0001@10.1.1.1>");
+}
+
+#[test]
+fn bare_numbers_only_in_party_headers() {
+    let mut redactor = Redactor::new();
+    let from = redactor.text("From: \"353871234567\" <sip:10.1.1.1>;tag=1");
+    assert_eq!(from, "From: \"353870000001\" <sip:10.1.1.1>;tag=1");
+
+    for line in &[
+        "o=- 1234567890 1234567891 IN IP4 10.1.1.1",
+        "Call-ID: 8745123490@10.1.1.1",
+        "Call-ID: 353871234567",
+    ] {
+        assert_eq!(redactor.text(line), *line);
+    }
+    assert_eq!(redactor.mapping().len(), 1);
+}
+
+#[test]
+fn ips_only_when_asked() {
+    let line =
+        "10:30:00.000 [ibcf] SIP RECV from 10.1.1.1:5060 to 10.2.2.2:5060";
+    assert_eq!(Redactor::new().text(line), line);
+
+    let mut redactor = Redactor::new().ips(true);
+    assert_eq!(
+        redactor.text(line),
+        "10:30:00.000 [ibcf] SIP RECV from 198.18.0.1:5060 to 198.18.0.2:5060"
+    );
+    assert_eq!(redactor.text("c=IN IP4 10.2.2.2"), "c=IN IP4 198.18.0.2");
+    assert_eq!(
+        redactor.mapping()[1],
+        ("10.2.2.2".to_owned(), "198.18.0.2".to_owned())
+    );
+}
+
+#[test]
+fn isup_numbers() {
+    let log = log("multipart");
+    let invite = &SipParser::new().extract_sip(&log, &[], true)[0];
+
+    let mut redactor = Redactor::new();
+    let redacted = redactor.packet(invite);
+    let iam = redacted.body().unwrap().parts()[1].isup().unwrap();
+    assert_eq!(iam.called.as_deref(), Some("+35310000001"));
+    assert_eq!(iam.calling.as_deref(), Some("+35310000002"));
+    assert_eq!(redacted.to_bytes().len(), invite.to_bytes().len());
+}
+
+#[test]
+fn isup_numbers_in_a_whole_log() {
+    let log = log("multipart");
+
+    let redacted = Redactor::new().log(&log);
+    assert_eq!(redacted.len(), log.len());
+    let invite = &SipParser::new().extract_sip(&redacted, &[], true)[0];
+    let iam = invite.body().unwrap().parts()[1].isup().unwrap();
+    assert_eq!(iam.called.as_deref(), Some("+35310000001"));
+    assert_eq!(iam.calling.as_deref(), Some("+35310000002"));
+    // Other ISUP messages are left as they are
+    assert!(redacted.contains("\n06 14 16 00\n"));
+}
@@ -0,0 +1,117 @@
+use cirtrace::{collapse_retransmissions, ColorChoice, HeaderFilter, Renderer};
+
+mod common;
+use common::packets;
+
+#[test]
+fn plain_rendering_matches_display() {
+    let renderer = Renderer::new(false).highlight(&["+35311234567"]);
+    for name in &["interleaved", "multipart", "framing", "retransmission"] {
+        for m in collapse_retransmissions(packets(name)) {
+            assert_eq!(renderer.message(&m), m.to_string());
+        }
+    }
+}
+
+#[test]
+fn start_lines_are_coloured_by_class() {
+    let renderer = Renderer::new(true);
+    let first_lines: Vec<_> = packets("call")
+        .iter()
+        .map(|p| renderer.packet(p).lines().next().unwrap().to_owned())
+        .collect();
+
+    assert!(first_lines[0].starts_with("\x1B[1;36mINVITE "));
+    assert!(first_lines[2].starts_with("\x1B[33mSIP/2.0 100"));
+    assert!(first_lines[4].starts_with("\x1B[32mSIP/2.0 200"));
+
+    let busy = packets("interleaved");
+    let busy = busy.iter().find(|p| p.status_code() == Some(486)).unwrap();
+    assert!(renderer.packet(busy).starts_with("\x1B[31mSIP/2.0 486"));
+}
+
+#[test]
+fn headers_are_emphasised_or_dimmed() {
+    let out = Renderer::new(true).packet(&packets("call")[0]);
+    assert!(out.contains("\x1B[1mCall-ID: call-c\x1B[0m\n"));
+    assert!(out.contains("\x1B[2mVia: SIP/2.0/UDP"));
+    assert!(out.contains("\nCSeq: 10 INVITE\n"));
+}
+
+#[test]
+fn search_terms_are_highlighted() {
+    let out = Renderer::new(true)
+        .highlight(&["+35311234567"])
+        .packet(&packets("call")[0]);
+    assert!(out.contains(
+        "\x1B[1mFrom: \"Alice\" <sip:\x1B[7m+35311234567\x1B[27m@10.1.1.1>"
+    ));
+}
+
+#[test]
+fn color_choice() {
+    assert!(ColorChoice::Always.enabled(false));
+    assert!(!ColorChoice::Never.enabled(true));
+    assert!(!ColorChoice::Auto.enabled(false));
+    assert_eq!(ColorChoice::parse("never"), Some(ColorChoice::Never));
+}
+
+#[test]
+fn header_whitelist_and_blacklist() {
+    let invite = &packets("call")[0];
+
+    let only = Renderer::new(false)
+        .headers(HeaderFilter::Only(vec!["call-id".into(), "CSeq".into()]))
+        .packet(invite);
+    assert_eq!(
+        only,
+        "INVITE sip:+35319876543@10.2.2.2 SIP/2.0\nCall-ID: call-c\nCSeq: 10 INVITE\n"
+    );
+
+    let hidden = Renderer::new(false)
+        .headers(HeaderFilter::Hide(vec![
+            "Via".into(),
+            "Content-Length".into(),
+        ]))
+        .packet(invite);
+    assert!(!hidden.contains("Via:"));
+    assert!(!hidden.contains("Content-Length:"));
+    assert!(hidden.contains("From: "));
+}
+
+#[test]
+fn folded_headers_go_with_the_header_they_continue() {
+    let mut log = common::log("call");
+    log = log.replacen(
+        "Call-ID: call-c\n",
+        "Call-ID: call-c\nSubject: a\n  <sip:folded@10.1.1.1>\n",
+        1,
+    );
+    let invite = &cirtrace::SipParser::new().parse(&log)[0];
+
+    let only = Renderer::new(false)
+        .headers(HeaderFilter::Only(vec!["Subject".into()]))
+        .packet(invite);
+    assert!(only.ends_with("\nSubject: a\n  <sip:folded@10.1.1.1>\n"));
+
+    let hidden = Renderer::new(false)
+        .headers(HeaderFilter::Hide(vec!["Subject".into()]))
+        .packet(invite);
+    assert!(!hidden.contains("folded"));
+    assert!(hidden.contains("Call-ID: call-c\n"));
+}
+
+#[test]
+fn compact_view() {
+    let invite = &packets("interleaved")[0];
+    assert_eq!(
+        Renderer::new(false).compact().packet(invite),
+        "INVITE sip:+35319876543@10.2.2.2 SIP/2.0
+From: <sip:+35311234567@10.1.1.1>;tag=f-call-1
+To: <sip:+35319876543@10.2.2.2>
+Call-ID: call-1
+CSeq: 1 INVITE
+SDP: c=IN IP4 10.1.1.1; m=audio 20000 RTP/AVP 8 101; a=sendrecv
+"
+    );
+}
@@ -0,0 +1,212 @@
+//! Golden tests for the SIP parser.
+//!
+//! Every `tests/fixtures/sip/<name>.log` is parsed and the output,
+//! as printed by the `sip` subcommand, compared against
+//! `<name>.expected`. Run with `CIRTRACE_BLESS=1` to regenerate the
+//! expected files after an intended change.
+
+use cirtrace::{Framing, SipParser};
+use std::{env, fs};
+
+mod common;
+use common::{fixture, log};
+
+fn golden(name: &str, terms: &[&str]) {
+    let log = log(name);
+    let expected_path = fixture(&format!("{}.expected", name));
+
+    let output: String = SipParser::new()
+        .extract_sip(&log, terms, true)
+        .iter()
+        .map(|p| format!("{}\n", p))
+        .collect();
+
+    if env::var_os("CIRTRACE_BLESS").is_some() {
+        fs::write(&expected_path, &output).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&expected_path).unwrap();
+    assert!(
+        output == expected,
+        "{} differs from {}:\n{}",
+        name,
+        expected_path.display(),
+        output
+    );
+}
+
+fn start_lines(name: &str, terms: &[&str]) -> Vec<String> {
+    let log = log(name);
+    SipParser::new()
+        .extract_sip(&log, terms, true)
+        .iter()
+        .map(|p| format!("{} [{}]", p.start_line(), p.call_id().unwrap()))
+        .collect()
+}
+
+#[test]
+fn interleaved_calls() {
+    golden("interleaved", &["+35319876543"]);
+
+    assert_eq!(
+        start_lines("interleaved", &["+35319876543"]),
+        [
+            "INVITE sip:+35319876543@10.2.2.2 SIP/2.0 [call-1]",
+            "INVITE sip:+35319876543@10.2.2.2 SIP/2.0 [call-2]",
+            "SIP/2.0 100 Trying [call-1]",
+            "SIP/2.0 100 Trying [call-2]",
+            "SIP/2.0 486 Busy Here [call-2]",
+            "SIP/2.0 180 Ringing [call-1]",
+            "ACK sip:+35319876543@10.2.2.2 SIP/2.0 [call-2]",
+            "SIP/2.0 200 OK [call-1]",
+            "ACK sip:+35319876543@10.2.2.2 SIP/2.0 [call-1]",
+        ]
+    );
+}
+
+#[test]
+fn interleaved_calls_single_match() {
+    assert_eq!(
+        start_lines("interleaved", &["+35318888888"]),
+        [
+            "INVITE sip:+35317777777@10.2.2.2 SIP/2.0 [call-x]",
+            "SIP/2.0 100 Trying [call-x]",
+        ]
+    );
+}
+
+#[test]
+fn no_terms_match_every_call() {
+    assert_eq!(start_lines("interleaved", &[]).len(), 11);
+}
+
+#[test]
+fn sdp_followed_by_log_lines() {
+    golden("sdp", &["+35311234567"]);
+    assert_eq!(
+        start_lines("sdp", &["+35311234567"]),
+        [
+            "INVITE sip:+35319876543@10.2.2.2 SIP/2.0 [call-s]",
+            "SIP/2.0 183 Session Progress [call-s]",
+            "SIP/2.0 200 OK [call-s]",
+        ]
+    );
+}
+
+#[test]
+fn retransmissions() {
+    golden("retransmission", &["+35311234567"]);
+    assert_eq!(start_lines("retransmission", &["+35311234567"]).len(), 7);
+}
+
+#[test]
+fn truncated_last_message() {
+    golden("truncated", &["+35311234567"]);
+    assert_eq!(
+        start_lines("truncated", &["+35311234567"]).last().unwrap(),
+        "SIP/2.0 180 Ringing [call-t]"
+    );
+}
+
+#[test]
+fn last_message_at_end_of_file() {
+    golden("eof", &["+35311234567"]);
+    assert_eq!(
+        start_lines("eof", &["+35311234567"]).last().unwrap(),
+        "SIP/2.0 200 OK [call-e]"
+    );
+}
+
+#[test]
+fn multipart_and_other_bodies() {
+    golden("multipart", &["+35311234567"]);
+
+    let log = log("multipart");
+    let packets = SipParser::new().extract_sip(&log, &[], true);
+    assert_eq!(packets.len(), 5);
+
+    let invite = packets[0].body().unwrap();
+    assert_eq!(invite.parts().len(), 2);
+    assert_eq!(packets[0].sdp().map(|s| s.len()), Some(8));
+    let iam = invite.parts()[1].isup().unwrap();
+    assert_eq!(iam.message_name(), "IAM");
+    assert_eq!(iam.called.as_deref(), Some("+35319876543"));
+    assert_eq!(iam.calling.as_deref(), Some("+35311234567"));
+
+    let acm = packets[1].body().unwrap().parts()[0].isup().unwrap();
+    assert_eq!(acm.message_name(), "ACM");
+
+    let info = &packets[2].body().unwrap().parts()[0];
+    assert_eq!(info.content_type(), Some("application/dtmf-relay"));
+    assert_eq!(info.lines(), ["Signal=5", "Duration=160"]);
+
+    // The blank line inside the PIDF document is part of the body
+    let pidf = &packets[3].body().unwrap().parts()[0];
+    assert_eq!(pidf.lines().len(), 7);
+    assert_eq!(pidf.lines().last().unwrap(), "</presence>");
+
+    let rel = packets[4].body().unwrap().parts()[0].isup().unwrap();
+    assert_eq!(rel.message_name(), "REL");
+    assert_eq!(rel.cause, Some(16));
+}
+
+#[test]
+fn content_length_framing() {
+    golden("framing", &["+35311234567"]);
+
+    let log = log("framing");
+    let framing: Vec<_> = SipParser::new()
+        .extract_sip(&log, &[], true)
+        .iter()
+        .map(|p| p.framing())
+        .collect();
+    assert_eq!(
+        framing,
+        [
+            Framing::Truncated {
+                declared: Some(124),
+                logged: 54
+            },
+            Framing::Complete,
+            Framing::LengthMismatch {
+                declared: 20,
+                logged: 65
+            },
+            Framing::Truncated {
+                declared: None,
+                logged: 0
+            },
+            Framing::Complete,
+        ]
+    );
+}
+
+#[test]
+fn line_endings_are_those_of_the_headers() {
+    // The trace keeps its CRs, the second message has none. Counted
+    // in CRLF, its body would end a line early.
+    let crlf = "10:26:00.000 [ibcf_core] SIP RECV from 10.1.1.1:5060 to \
+                10.2.2.2:5060\n\
+                INFO sip:+35319876543@10.2.2.2 SIP/2.0\r\n\
+                Call-ID: call-crlf\r\n\
+                CSeq: 2 INFO\r\n\
+                Content-Type: text/plain\r\n\
+                Content-Length: 4\r\n\
+                \r\n\
+                ab\r\n";
+    let lf = "10:26:01.000 [ibcf_core] SIP RECV from 10.1.1.1:5060 to \
+              10.2.2.2:5060\n\
+              INFO sip:+35319876543@10.2.2.2 SIP/2.0\n\
+              Call-ID: call-lf\n\
+              CSeq: 3 INFO\n\
+              Content-Type: text/plain\n\
+              Content-Length: 12\n\
+              \n\
+              ab\ncd\nef\ngh\n";
+    let packets = SipParser::new().parse(&format!("{}{}", crlf, lf));
+
+    assert_eq!(packets.len(), 2);
+    assert!(packets.iter().all(|p| p.framing() == Framing::Complete));
+    assert!(packets[1].to_string().contains("\n\nab\ncd\nef\ngh\n"));
+}
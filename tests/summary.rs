@@ -0,0 +1,96 @@
+use cirtrace::{summarize, Party, SipParser, TimeOfDay};
+use std::time::Duration;
+
+mod common;
+use common::log;
+
+fn summaries(name: &str, terms: &[&str]) -> Vec<cirtrace::CallSummary> {
+    summarize(&SipParser::new().extract_sip(&log(name), terms, true))
+}
+
+#[test]
+fn answered_call_cleared_by_callee() {
+    let calls = summaries("call", &["+35311234567"]);
+    assert_eq!(calls.len(), 1);
+
+    let call = &calls[0];
+    assert_eq!(call.caller.as_deref(), Some("+35311234567"));
+    assert_eq!(call.callee.as_deref(), Some("+35319876543"));
+    assert_eq!(call.invite_time, TimeOfDay::parse("10:30:00.000"));
+    assert_eq!(call.pdd(), Some(Duration::from_millis(2250)));
+    assert_eq!(call.duration(), Some(Duration::from_millis(42300)));
+    assert_eq!(call.end_method.as_deref(), Some("BYE"));
+    assert_eq!(call.ended_by, Some(Party::Callee));
+    assert_eq!(call.final_status, Some(200));
+    assert_eq!(
+        call.reason.as_deref(),
+        Some("Q.850;cause=16;text=\"Normal call clearing\"")
+    );
+    assert_eq!(call.retransmissions, 1);
+
+    assert_eq!(
+        call.to_string(),
+        "Call-ID:         call-c
+Caller:          +35311234567
+Callee:          +35319876543
+INVITE:          10:30:00.000
+Ringing:         10:30:02.250 (PDD 2.250s)
+Answered:        10:30:05.000
+Ended:           10:30:47.300 by callee (BYE)
+Duration:        42.300s
+Final status:    200 OK
+Reason:          Q.850;cause=16;text=\"Normal call clearing\"
+Retransmissions: 1
+"
+    );
+}
+
+#[test]
+fn challenged_call_is_followed_to_the_answer() {
+    // A 407 to the first INVITE, sent again with CSeq 11
+    let log = log("call");
+    let (first, rest) = log.split_at(log.find("10:30:00.500").unwrap());
+    let challenge = first
+        .replace("10:30:00.000", "10:30:00.100")
+        .replace(
+            "RECV from 10.1.1.1:5060 to 10.2.2.2:5060",
+            "SEND from 10.2.2.2:5060 to 10.1.1.1:5060",
+        )
+        .replace(
+            "INVITE sip:+35319876543@10.2.2.2 SIP/2.0",
+            "SIP/2.0 407 Proxy Authentication Required",
+        );
+    let log = format!(
+        "{}{}{}",
+        first,
+        challenge,
+        rest.replace("CSeq: 10 ", "CSeq: 11 ")
+    );
+
+    let calls = summarize(&SipParser::new().extract_sip(&log, &[], true));
+    assert_eq!(calls.len(), 1);
+    let call = &calls[0];
+    assert_eq!(call.final_status, Some(200));
+    assert_eq!(call.final_status_text.as_deref(), Some("OK"));
+    assert!(call.answered());
+    assert_eq!(call.duration(), Some(Duration::from_millis(42300)));
+    assert_eq!(call.retransmissions, 0);
+}
+
+#[test]
+fn rejected_call() {
+    let calls = summaries("interleaved", &["+35319876543"]);
+    assert_eq!(calls.len(), 2);
+
+    let busy = calls.iter().find(|c| c.call_id == "call-2").unwrap();
+    assert_eq!(busy.final_status, Some(486));
+    assert_eq!(busy.final_status_text.as_deref(), Some("Busy Here"));
+    assert_eq!(busy.reason.as_deref(), Some("Q.850;cause=17"));
+    assert!(!busy.answered());
+    assert_eq!(busy.duration(), None);
+
+    let answered = calls.iter().find(|c| c.call_id == "call-1").unwrap();
+    assert_eq!(answered.pdd(), Some(Duration::from_millis(2100)));
+    assert!(answered.answered());
+    assert_eq!(answered.end_method, None);
+}
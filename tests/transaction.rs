@@ -0,0 +1,52 @@
+use cirtrace::{collapse_retransmissions, find_issues, IssueKind};
+
+mod common;
+use common::packets;
+
+#[test]
+fn retransmissions_are_collapsed() {
+    let messages = collapse_retransmissions(packets("retransmission"));
+    let counts: Vec<_> = messages
+        .iter()
+        .map(|m| (m.packet.start_line(), m.retransmissions.len()))
+        .collect();
+    assert_eq!(
+        counts,
+        [
+            ("INVITE sip:+35319876543@10.2.2.2 SIP/2.0", 2),
+            ("SIP/2.0 100 Trying", 0),
+            ("SIP/2.0 200 OK", 1),
+            ("ACK sip:+35319876543@10.2.2.2 SIP/2.0", 0),
+        ]
+    );
+    assert!(messages[0]
+        .to_string()
+        .ends_with("[retransmitted 2 times, last at 10:22:01.500]\n"));
+    assert!(messages[2]
+        .to_string()
+        .ends_with("[retransmitted 1 time, last at 10:22:03.500]\n"));
+}
+
+#[test]
+fn complete_transactions_have_no_issues() {
+    assert_eq!(find_issues(&packets("retransmission")), []);
+    assert_eq!(find_issues(&packets("call")), []);
+}
+
+#[test]
+fn unanswered_and_unacked_transactions() {
+    let issues: Vec<_> = find_issues(&packets("interleaved"))
+        .into_iter()
+        .map(|i| (i.call_id, i.kind))
+        .collect();
+    assert_eq!(issues, [("call-x".to_owned(), IssueKind::NoFinalResponse)]);
+
+    let issues = find_issues(&packets("truncated"));
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].kind, IssueKind::NoFinalResponse);
+    assert_eq!(
+        issues[0].to_string(),
+        "[call-t] INVITE sip:+35319876543@10.2.2.2 SIP/2.0 at 10:23:00.000: \
+         no final response"
+    );
+}
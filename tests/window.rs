@@ -0,0 +1,110 @@
+use cirtrace::log_entry::{capture_start, log_blocks};
+use cirtrace::window::parse_duration;
+use cirtrace::{Error, SipParser, TimeBound, TimeOfDay, TimeWindow};
+use std::time::Duration;
+
+mod common;
+use common::log;
+
+fn window(since: Option<&str>, until: Option<&str>) -> TimeWindow {
+    TimeWindow {
+        since: since.map(|s| TimeBound::parse(s).unwrap()),
+        until: until.map(|u| TimeBound::parse_until(u).unwrap()),
+    }
+}
+
+fn start_lines(name: &str, window: &TimeWindow) -> Vec<String> {
+    let log = log(name);
+    let start = capture_start(&log).unwrap();
+    let packets = SipParser::new().extract(&log, &cirtrace::Filter::All);
+    window
+        .packets(packets, start)
+        .iter()
+        .map(|p| p.start_line().to_owned())
+        .collect()
+}
+
+#[test]
+fn bounds() {
+    assert_eq!(
+        TimeBound::parse("10:15:20").unwrap(),
+        TimeBound::At(TimeOfDay::parse("10:15:20").unwrap())
+    );
+    assert_eq!(
+        TimeBound::parse("+1m30s").unwrap(),
+        TimeBound::AfterStart(Duration::from_secs(90))
+    );
+    assert_eq!(
+        TimeBound::parse_until("10:31").unwrap(),
+        TimeBound::At(TimeOfDay::parse("10:31:59.999").unwrap())
+    );
+    assert_eq!(
+        TimeBound::parse_until("10:31:20").unwrap(),
+        TimeBound::parse("10:31:20").unwrap()
+    );
+    assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3600)));
+    assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+    assert_eq!(parse_duration("5x"), None);
+    assert_eq!(parse_duration(""), None);
+    assert!(matches!(
+        TimeBound::parse("25:00"),
+        Err(Error::InvalidTime(_))
+    ));
+    for bound in &["+soon", "+"] {
+        assert!(matches!(
+            TimeBound::parse(bound),
+            Err(Error::InvalidTime(_))
+        ));
+    }
+}
+
+#[test]
+fn absolute_window_on_sip_messages() {
+    assert_eq!(
+        start_lines("call", &window(Some("10:30:02"), Some("10:30:05.100"))),
+        [
+            "SIP/2.0 180 Ringing",
+            "SIP/2.0 200 OK",
+            "ACK sip:+35319876543@10.2.2.2 SIP/2.0",
+        ]
+    );
+}
+
+#[test]
+fn relative_window_on_sip_messages() {
+    let lines = start_lines("call", &window(Some("+40s"), None));
+    assert_eq!(
+        lines,
+        ["BYE sip:+35311234567@10.1.1.1 SIP/2.0", "SIP/2.0 200 OK"]
+    );
+
+    // A bound before the capture started doesn't wrap to the next day
+    assert_eq!(start_lines("call", &window(Some("09:00"), None)).len(), 8);
+    assert_eq!(start_lines("call", &window(None, Some("09:00"))).len(), 0);
+}
+
+#[test]
+fn window_on_log_blocks() {
+    let log = log("interleaved");
+    let blocks = log_blocks(&log);
+    assert_eq!(blocks.len(), 13);
+    assert_eq!(
+        blocks[0].lines,
+        ["10:20:00.000 [ibcf_core] INFO timer wheel tick"]
+    );
+
+    let start = capture_start(&log).unwrap();
+    let kept = window(Some("+3s"), Some("10:20:05")).blocks(blocks, start);
+    let first_lines: Vec<_> = kept.iter().map(|b| b.lines[0]).collect();
+    assert_eq!(
+        first_lines,
+        [
+            "10:20:03.000 [ibcf_core] SIP SEND from 10.2.2.2:5060 to 10.1.1.1:5060",
+            "10:20:03.100 [ibcf_core] SIP SEND from 10.2.2.2:5060 to 10.1.1.1:5060",
+            "10:20:03.200 [ibcf_core] SIP RECV from 10.1.1.1:5060 to 10.2.2.2:5060",
+            "10:20:05.000 [ibcf_core] SIP SEND from 10.2.2.2:5060 to 10.1.1.1:5060",
+        ]
+    );
+    assert!(kept[0].to_string().contains("SIP/2.0 486 Busy Here\n"));
+}